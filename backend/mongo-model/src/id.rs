@@ -1,7 +1,7 @@
 use crate::Model;
 use bson::oid::ObjectId;
 use bson::Bson;
-use derive_where::{derive_where, DeriveWhere};
+use derive_where::DeriveWhere;
 use serde::{Deserialize, Serialize};
 use std::fmt;
 use std::fmt::{Display, Formatter};
@@ -11,13 +11,13 @@ use std::str::FromStr;
 #[derive(Serialize, Deserialize, DeriveWhere)]
 #[derive_where(Default, Debug, Copy, Clone, PartialEq, PartialOrd, Eq, Ord, Hash)]
 #[serde(transparent)]
-pub struct Id<T: Model + ?Sized> {
+pub struct Id<T: Model> {
     pub id: ObjectId,
     #[derive_where(skip)]
     pub _phantom: PhantomData<fn() -> T>,
 }
 
-impl<T: Model + ?Sized> Id<T> {
+impl<T: Model> Id<T> {
     pub fn new() -> Self {
         Id {
             id: ObjectId::new(),
@@ -26,13 +26,13 @@ impl<T: Model + ?Sized> Id<T> {
     }
 }
 
-impl<T: Model + ?Sized> Display for Id<T> {
+impl<T: Model> Display for Id<T> {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         write!(f, "{}[{}]", T::COLLECTION, self.id)
     }
 }
 
-impl<T: Model + ?Sized> From<ObjectId> for Id<T> {
+impl<T: Model> From<ObjectId> for Id<T> {
     fn from(id: ObjectId) -> Self {
         Id {
             id,
@@ -41,19 +41,19 @@ impl<T: Model + ?Sized> From<ObjectId> for Id<T> {
     }
 }
 
-impl<T: Model + ?Sized> From<Id<T>> for ObjectId {
+impl<T: Model> From<Id<T>> for ObjectId {
     fn from(id: Id<T>) -> Self {
         id.id
     }
 }
 
-impl<T: Model + ?Sized> From<Id<T>> for Bson {
+impl<T: Model> From<Id<T>> for Bson {
     fn from(id: Id<T>) -> Self {
         id.id.into()
     }
 }
 
-impl<T: Model + ?Sized> FromStr for Id<T> {
+impl<T: Model> FromStr for Id<T> {
     type Err = bson::oid::Error;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
@@ -0,0 +1,136 @@
+use std::time::{Duration, Instant};
+
+use bson::doc;
+use clap::Args;
+use eyre::{eyre, OptionExt};
+use futures::future::try_join_all;
+use lsp_types::{Position, TextDocumentIdentifier, TextDocumentPositionParams, Url};
+use mongodb::options::FindOptions;
+use shatterbird_storage::model::lang::{EdgeInfoDiscriminants, VertexInfoDiscriminants};
+use shatterbird_storage::model::{NavigationRange, Range, Vertex};
+use shatterbird_storage::{util, Storage};
+
+use crate::App;
+
+/// Compares answering definitions at ranges with precomputed navigation the old and the new way.
+///
+/// Both sides go through the whole path of an `/api/lsp` definition request: the position is
+/// resolved with `util::graph::find` and the results are turned into locations. "graph walk" then
+/// follows the stored graph and calls `to_location` for every result, which is what the server
+/// did before navigation was precomputed, and "navigation table" reads the stored records, which
+/// is what it does now. The two lines are the before and after numbers for the same positions.
+/// Run `shatterbird-indexer migrate` first: both sides are only meaningful with indexes in place.
+#[derive(Args)]
+pub struct Bench {
+    /// How many ranges with precomputed navigation to query
+    #[arg(long, default_value = "100")]
+    samples: u32,
+}
+
+impl Bench {
+    pub async fn run(self, app: App) -> eyre::Result<()> {
+        let samples = app
+            .storage
+            .find::<NavigationRange>(
+                None,
+                FindOptions::builder().limit(self.samples as i64).build(),
+            )
+            .await?;
+        if samples.is_empty() {
+            return Err(eyre!("no navigation records found, import a dump first"));
+        }
+
+        let mut walk = Vec::new();
+        let mut table = Vec::new();
+        for sample in &samples {
+            let position = position(&app.storage, sample).await?;
+
+            let start = Instant::now();
+            walk_graph(&app.storage, &position).await?;
+            walk.push(start.elapsed());
+
+            let start = Instant::now();
+            let found = util::graph::find(
+                &app.storage,
+                Some(EdgeInfoDiscriminants::Definition),
+                &position,
+            )
+            .await?;
+            let locations = found
+                .navigation
+                .into_iter()
+                .flat_map(|x| x.definitions)
+                .map(|x| x.location)
+                .collect::<Vec<_>>();
+            table.push(start.elapsed());
+            if locations.is_empty() && found.found.is_empty() {
+                return Err(eyre!("no definitions found for {}", sample.range));
+            }
+        }
+
+        println!("{} ranges", samples.len());
+        print_stats("graph walk", walk);
+        print_stats("navigation table", table);
+        Ok(())
+    }
+}
+
+/// Position of the start of the sampled range in the commit of its file
+async fn position(
+    storage: &Storage,
+    sample: &NavigationRange,
+) -> eyre::Result<TextDocumentPositionParams> {
+    let range = storage
+        .get(sample.range)
+        .await?
+        .ok_or_eyre(eyre!("range {} disappeared", sample.range))?;
+    let path = util::graph::find_file_path(storage, &range).await?;
+    let (line, _) = util::graph::find_line_nos(storage, &range).await?;
+    let mut uri = Url::parse("file:///")?;
+    uri.path_segments_mut()
+        .map_err(|()| eyre!("file URIs always have a path"))?
+        .extend(&path);
+    Ok(TextDocumentPositionParams {
+        text_document: TextDocumentIdentifier { uri },
+        position: Position::new(line, range.start),
+    })
+}
+
+/// The same queries `/api/lsp` did for a definition before navigation was precomputed
+async fn walk_graph(storage: &Storage, position: &TextDocumentPositionParams) -> eyre::Result<()> {
+    let found = util::graph::find(storage, None, position).await?;
+    let range = found
+        .ranges
+        .first()
+        .ok_or_eyre(eyre!("no range found at {:?}", position))?;
+    let vertex = storage
+        .find_one::<Vertex>(
+            doc! {
+                "data.vertex": "Range",
+                "data.range": range,
+            },
+            None,
+        )
+        .await?
+        .ok_or_eyre(eyre!("no vertex found for {}", range))?;
+    let found =
+        util::graph::follow(storage, vertex.id, EdgeInfoDiscriminants::Definition.into()).await?;
+    let results = util::graph::filter_vertices(found, VertexInfoDiscriminants::DefinitionResult);
+    let items: Vec<Range> = util::graph::find_items(storage, results.map(|x| x.id)).await?;
+    try_join_all(items.iter().map(|x| util::graph::to_location(storage, x))).await?;
+    Ok(())
+}
+
+fn print_stats(name: &str, mut times: Vec<Duration>) {
+    times.sort_unstable();
+    let total = times.iter().sum::<Duration>();
+    let percentile = |p: usize| times[(times.len() - 1) * p / 100];
+    println!(
+        "{}: mean {:?}, p50 {:?}, p95 {:?}, max {:?}",
+        name,
+        total / times.len() as u32,
+        percentile(50),
+        percentile(95),
+        percentile(100),
+    );
+}
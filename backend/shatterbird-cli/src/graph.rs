@@ -10,6 +10,7 @@ use shatterbird_storage::model::lang::{
     EdgeInfoDiscriminants, VertexInfo, VertexInfoDiscriminants,
 };
 use shatterbird_storage::model::{Edge, Range, Vertex};
+use shatterbird_storage::util::encoding;
 use shatterbird_storage::{util, Id};
 use std::collections::{HashMap, HashSet};
 use std::io::{BufWriter, Write};
@@ -170,21 +171,31 @@ impl<W: Write> State<W> {
                 .get(*range)
                 .await?
                 .ok_or_eyre(eyre!("{range} not found"))?;
-            let line_no = 1 + util::graph::find_line_no(storage, &range).await?;
+            let (start_no, end_no) = util::graph::find_line_nos(storage, &range).await?;
+            let (start_no, end_no) = (start_no + 1, end_no + 1);
             let filename = util::graph::find_file_path(storage, &range).await?;
             let filename = &filename[1..].join("/");
             let line = storage
-                .get(range.line_id)
+                .get(range.start_line)
                 .await?
-                .ok_or_eyre(eyre!("line {} not found", range.line_id))?;
-            let start = range.start.min(line.text.len() as _) as _;
-            let end = range.end.min(line.text.len() as _) as _;
+                .ok_or_eyre(eyre!("line {} not found", range.start_line))?;
+            let end = if range.is_single_line() {
+                range.end
+            } else {
+                u32::MAX
+            };
+            let start = encoding::byte_offset(&line.text, range.start);
+            let end = encoding::byte_offset(&line.text, end).max(start);
             let span = line.text[start..end].to_string();
-            xlabel = Some(format!("«{span}»@L{line_no}"));
-            tooltip = Some(format!(
-                "{filename}:{line_no}:{}-{}",
-                range.start, range.end
-            ));
+            xlabel = Some(format!("«{span}»@L{start_no}"));
+            tooltip = Some(if range.is_single_line() {
+                format!("{filename}:{start_no}:{}-{}", range.start, range.end)
+            } else {
+                format!(
+                    "{filename}:{start_no}:{}-{end_no}:{}",
+                    range.start, range.end
+                )
+            });
         }
 
         let fillcolor = if root {
@@ -1,3 +1,4 @@
+mod bench;
 mod graph;
 
 use clap::{Parser, Subcommand};
@@ -19,6 +20,7 @@ struct Options {
 #[derive(Subcommand)]
 enum Command {
     Graph(graph::Graph),
+    Bench(bench::Bench),
 }
 
 pub struct App {
@@ -44,5 +46,6 @@ async fn main() -> eyre::Result<()> {
     };
     match opts.command {
         Command::Graph(graph) => graph.run(app).await,
+        Command::Bench(bench) => bench.run(app).await,
     }
 }
@@ -2,9 +2,9 @@ use either::Either;
 use std::collections::HashMap;
 use std::path::Path;
 
-use eyre::{eyre};
+use eyre::eyre;
 use futures::FutureExt;
-use gix::bstr::{BString};
+use gix::bstr::BString;
 use gix::object::Kind;
 use gix::{ObjectId, Repository};
 use tracing::{debug, debug_span, instrument, warn, Instrument};
@@ -247,13 +247,22 @@ pub async fn index(storage: &Storage, root: &Path, max_depth: u32) -> eyre::Resu
     let mut head = repo.head()?;
     let commit = head.peel_to_commit_in_place()?;
 
+    let result = index_commit(storage, &repo, commit, max_depth).await?;
+
+    println!("{}", result);
+    Ok(())
+}
+
+pub async fn index_commit(
+    storage: &Storage,
+    repo: &Repository,
+    commit: gix::Commit<'_>,
+    max_depth: u32,
+) -> eyre::Result<Id<Commit>> {
     let mut indexer = Walker {
         storage,
-        repo: &repo,
+        repo,
         path: RepoPath::default(),
     };
-    let result = indexer.visit_commit(commit, max_depth).await?;
-
-    println!("{}", result);
-    Ok(())
+    indexer.visit_commit(commit, max_depth).await
 }
@@ -1,51 +1,100 @@
+use bson::doc;
 use either::Either;
 use eyre::{eyre, OptionExt};
 use futures::future::join_all;
-use std::hash::Hash;
 
-use futures::FutureExt;
-use gix::ObjectId;
 use rayon::prelude::*;
-use scc::{HashMap};
+use scc::HashMap;
+use std::sync::Mutex;
 use tracing::{debug, debug_span, info, info_span, instrument, trace, warn, Level};
 
-use crate::lsif::RootMapping;
 use lsp_types::lsif;
 use radix_trie::{Trie, TrieCommon};
 use scc::hash_map::Entry;
-use shatterbird_storage::model::lang::{EdgeData, EdgeDataMultiIn, EdgeInfo, Item, VertexInfo};
-use shatterbird_storage::model::{Commit, Edge, FileContent, Line, Node, Range, Vertex};
+use shatterbird_storage::model::lang::{
+    DocumentSymbolOrRangeBasedVec, EdgeData, EdgeDataMultiIn, EdgeInfo, EdgeInfoDiscriminants,
+    Item, RangeBasedDocumentSymbol, VertexInfo, VertexInfoDiscriminants,
+};
+use shatterbird_storage::model::{
+    Commit, Edge, FileContent, Line, Navigation, NavigationRange, NavigationTarget, Node, Range,
+    Symbol, Upload, UploadRoot, Vertex,
+};
+use shatterbird_storage::util::encoding::{self, Encoding};
 use shatterbird_storage::{Id, Model, Storage};
 
 use super::graph::{DocumentRef, EdgeRef, Graph, VertexRef};
 use super::lsif_ext::{EdgeDataRef, EdgeExtensions};
+use super::report::ImportReport;
+use super::roots::{prefix_key, uri_key};
 
+/// Separators of components in moniker identifiers of common indexers
+const MONIKER_SEPARATORS: [char; 4] = [':', '.', '/', '#'];
+
+/// Stored file a document of the dump is resolved to
 #[derive(Debug)]
-struct FileWithPath {
-    node: Node,
-    path: Vec<Id<Node>>,
+pub(super) struct FileWithPath {
+    /// Id of the `document` vertex
+    pub id: lsif::Id,
+    pub document: lsif::Document,
+    pub node: Node,
+    pub path: Vec<Id<Node>>,
+    /// Texts of the lines, loaded only when positions have to be converted to another encoding
+    pub texts: std::collections::HashMap<Id<Line>, String>,
+    pub uri: lsp_types::Url,
+    /// URI of the document in the stored commit, `bird:///<commit>/<path>`
+    pub location: lsp_types::Url,
+    pub commit: Id<Commit>,
 }
 
 pub struct Converter<'g, 's> {
     storage: &'s Storage,
     graph: &'g Graph<'g>,
-    roots: Trie<String, Either<Id<Commit>, ObjectId>>,
+    roots: Trie<String, Id<Commit>>,
+    schemes: Vec<String>,
+    upload: Id<Upload>,
+    encoding: Encoding,
     files: HashMap<lsif::Id, FileWithPath>,
     ranges: HashMap<lsif::Id, Range>,
+    locations: HashMap<lsif::Id, lsp_types::Location>,
     vertices: HashMap<lsif::Id, Option<Vertex>>,
     edges: HashMap<lsif::Id, Either<Id<Edge>, Edge>>,
+    report: Mutex<ImportReport>,
 }
 
 impl<'g, 's> Converter<'g, 's> {
-    pub fn new(storage: &'s Storage, graph: &'g Graph, roots: Vec<RootMapping>) -> Self {
+    pub fn new(
+        storage: &'s Storage,
+        graph: &'g Graph,
+        roots: &[UploadRoot],
+        schemes: Vec<String>,
+        upload: Id<Upload>,
+    ) -> Self {
+        let encoding = match graph.metadata() {
+            Some(x) => x.position_encoding,
+            None => {
+                warn!(
+                    "no metaData vertex found, assuming positions are in {:?}",
+                    encoding::CANONICAL
+                );
+                encoding::CANONICAL
+            }
+        };
         Converter {
             storage,
             graph,
-            roots: roots.into_iter().map(|x| (x.dir, x.node)).collect(),
+            roots: roots
+                .iter()
+                .map(|x| (prefix_key(&x.prefix), x.commit))
+                .collect(),
+            schemes,
+            upload,
+            encoding,
             files: HashMap::new(),
             ranges: HashMap::new(),
+            locations: HashMap::new(),
             vertices: HashMap::new(),
             edges: HashMap::new(),
+            report: Mutex::new(ImportReport::default()),
         }
     }
 
@@ -56,85 +105,467 @@ impl<'g, 's> Converter<'g, 's> {
             .graph
             .documents()
             .into_par_iter()
-            .map(|doc| {
-                let doc_id = doc.entry().id.clone();
-                self.load_doc(doc).map(|x| (doc_id, x))
-            })
+            .map(|doc| self.resolve_file(doc))
             .collect_vec_list();
-        let docs = join_all(tasks.into_iter().flatten())
+        let files = join_all(tasks.into_iter().flatten())
             .await
             .into_iter()
-            .filter_map(|(doc_id, x)| match x {
-                Ok(None) => None,
-                Ok(Some(x)) => Some(Ok((doc_id, x))),
-                Err(e) => Some(Err(e)),
-            })
+            .filter_map(|x| x.transpose())
             .collect::<Result<Vec<_>, _>>()?;
 
         // CPU-bound part
+        self.load_files(files)
+    }
+
+    /// Loads documents of the dump, given the stored files they are resolved to
+    pub(super) fn load_files(&self, files: Vec<FileWithPath>) -> eyre::Result<()> {
+        let docs = files
+            .into_par_iter()
+            .map(|file| self.load_doc(file))
+            .collect::<Result<Vec<_>, _>>()?;
         docs.into_par_iter()
             .map(|(id, v)| self.load_children(&id, v))
             .collect::<Result<Vec<_>, _>>()?;
+        self.load_attachments()?;
         Ok(())
     }
 
+    /// Monikers attached to loaded ones are not reachable from documents, so they are loaded separately
+    fn load_attachments(&self) -> eyre::Result<()> {
+        for edge in self.graph.attachments() {
+            let data = match edge.edge() {
+                lsif::Edge::Attach(x) => x,
+                _ => continue,
+            };
+            let attached = self
+                .vertices
+                .read(&data.in_v, |_, v| v.is_some())
+                .unwrap_or(false);
+            if !attached {
+                continue;
+            }
+            if let Some(out_v) = self.load_vertex(&data.out_v)? {
+                self.load_edge(out_v, *edge)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Summarizes what was loaded so far.
+    pub fn report(&self) -> ImportReport {
+        let mut report = std::mem::take(&mut *self.report.lock().unwrap());
+        report.documents_resolved = self.files.len();
+        report.events_dropped = self.graph.events();
+        self.vertices.scan(|_, v| {
+            if let Some(v) = v {
+                let kind = VertexInfoDiscriminants::from(&v.data).into();
+                *report.vertices.entry(kind).or_default() += 1;
+            }
+        });
+        self.edges.scan(|_, e| {
+            if let Either::Right(e) = e {
+                let kind = EdgeInfoDiscriminants::from(&e.data).into();
+                *report.edges.entry(kind).or_default() += 1;
+            }
+        });
+        report
+    }
+
+    /// Ranges, vertices and edges converted from the dump
+    pub(super) fn contents(&self) -> (Vec<Range>, Vec<Vertex>, Vec<Edge>) {
+        let (mut ranges, mut vertices, mut edges) = (Vec::new(), Vec::new(), Vec::new());
+        self.ranges.scan(|_, x| ranges.push(x.clone()));
+        self.vertices.scan(|_, x| vertices.extend(x.clone()));
+        self.edges.scan(|_, x| {
+            if let Either::Right(edge) = x {
+                edges.push(edge.clone())
+            }
+        });
+        (ranges, vertices, edges)
+    }
+
     #[instrument(skip_all, err)]
     pub async fn save(self) -> eyre::Result<()> {
+        let (ranges, vertices, edges) = self.contents();
         tokio::try_join!(
             async {
                 let _span = info_span!("saving ranges").entered();
-                let mut ranges = Vec::new();
-                let mut next = self.ranges.first_entry_async().await;
-                while let Some(curr) = next {
-                    ranges.push(curr.get().clone());
-                    next = curr.next_async().await
-                }
                 info!("saving {} ranges", ranges.len());
                 self.storage.insert_many(ranges.iter()).await
             },
             async {
                 let _span = info_span!("saving vertices").entered();
-                let mut vertices = Vec::new();
-                let mut next = self.vertices.first_entry_async().await;
-                while let Some(curr) = next {
-                    if let Some(vertex) = curr.get().as_ref() {
-                        vertices.push(vertex.clone());
-                    }
-                    next = curr.next_async().await
-                }
                 info!("saving {} vertices", vertices.len());
                 self.storage.insert_many(vertices.iter()).await
             },
             async {
                 let _span = info_span!("saving edges").entered();
-                let mut edges = Vec::new();
-                let mut next = self.edges.first_entry_async().await;
-                while let Some(curr) = next {
-                    if let Either::Right(edge) = curr.get().as_ref() {
-                        edges.push(edge.clone());
-                    }
-                    next = curr.next_async().await
-                }
                 info!("saving {} edges", edges.len());
-                self.storage.access().insert_many(edges, None).await?;
+                self.storage.insert_many(edges.iter()).await
+            },
+            async {
+                let _span = info_span!("saving symbols").entered();
+                let symbols = self.symbols();
+                info!("saving {} symbols", symbols.len());
+                if !symbols.is_empty() {
+                    self.storage.insert_many(symbols.iter()).await?;
+                }
+                Ok(())
+            },
+            async {
+                let _span = info_span!("saving navigation").entered();
+                let (navigation, ranges) = self.navigation();
+                info!(
+                    "saving navigation for {} ranges in {} records",
+                    ranges.len(),
+                    navigation.len()
+                );
+                if !navigation.is_empty() {
+                    self.storage.insert_many(navigation.iter()).await?;
+                    self.storage.insert_many(ranges.iter()).await?;
+                }
                 Ok(())
             }
         )?;
         Ok(())
     }
 
+    /// Workspace symbol index: ranges with definition tags and symbols of documents,
+    /// nested in the symbols or named by monikers they belong to.
+    fn symbols(&self) -> Vec<Symbol> {
+        let mut docs = Vec::new();
+        self.files
+            .scan(|k, v| docs.push((k.clone(), v.commit, v.location.clone())));
+        docs.into_par_iter()
+            .flat_map_iter(|(doc_id, commit, location)| {
+                self.document_symbols_index(&doc_id, commit, &location)
+            })
+            .collect()
+    }
+
+    fn document_symbols_index(
+        &self,
+        doc_id: &lsif::Id,
+        commit: Id<Commit>,
+        location: &lsp_types::Url,
+    ) -> Vec<Symbol> {
+        let edges = self
+            .graph
+            .outgoing_from(doc_id)
+            .into_par_iter()
+            .collect::<Vec<_>>();
+        let symbol = |name: &str, kind, container: Option<String>, location, range| Symbol {
+            id: Id::new(),
+            name: name.to_string(),
+            lowercase_name: name.to_lowercase(),
+            kind,
+            container,
+            location,
+            range,
+            commit,
+            upload: self.upload,
+        };
+
+        // Containers of ranges referenced by range-based document symbols
+        let mut containers = std::collections::HashMap::new();
+        fn visit_ranges<'a>(
+            graph: &'a Graph,
+            symbols: &'a [lsif::RangeBasedDocumentSymbol],
+            parent: Option<&'a str>,
+            containers: &mut std::collections::HashMap<&'a lsif::Id, &'a str>,
+        ) {
+            for symbol in symbols {
+                if let Some(parent) = parent {
+                    containers.insert(&symbol.id, parent);
+                }
+                let name = graph.vertex(&symbol.id).and_then(|x| match x.vertex() {
+                    lsif::Vertex::Range {
+                        tag: Some(lsif::RangeTag::Definition(tag)),
+                        ..
+                    } => Some(tag.text.as_str()),
+                    lsif::Vertex::Range {
+                        tag: Some(lsif::RangeTag::Declaration(tag)),
+                        ..
+                    } => Some(tag.text.as_str()),
+                    _ => None,
+                });
+                visit_ranges(graph, &symbol.children, name.or(parent), containers);
+            }
+        }
+        let mut full = Vec::new();
+        for edge in &edges {
+            let result = match edge.edge() {
+                lsif::Edge::DocumentSymbol(x) => &x.in_v,
+                _ => continue,
+            };
+            if let Some(lsif::Vertex::DocumentSymbolResult {
+                result: lsif::DocumentSymbolOrRangeBasedVec::RangeBased(symbols),
+            }) = self.graph.vertex(result).map(|x| x.vertex())
+            {
+                visit_ranges(self.graph, symbols, None, &mut containers);
+            }
+            // Positions of full symbols are taken from the stored vertex, where they are normalized
+            if let Some(Some(Vertex {
+                data:
+                    VertexInfo::DocumentSymbolResult {
+                        result: DocumentSymbolOrRangeBasedVec::DocumentSymbol(symbols),
+                    },
+                ..
+            })) = self.vertices.get(result).map(|x| x.get().clone())
+            {
+                full.extend(symbols);
+            }
+        }
+
+        let mut result = Vec::new();
+        for edge in &edges {
+            let ranges = match edge.edge() {
+                lsif::Edge::Contains(x) => &x.in_vs,
+                _ => continue,
+            };
+            for vertex in ranges {
+                let tag = match self.graph.vertex(vertex).map(|x| x.vertex()) {
+                    Some(lsif::Vertex::Range {
+                        tag: Some(lsif::RangeTag::Definition(tag)),
+                        ..
+                    }) => tag,
+                    _ => continue,
+                };
+                let (range, location) = match (self.ranges.get(vertex), self.locations.get(vertex))
+                {
+                    (Some(range), Some(location)) => (range.get().id, location.get().clone()),
+                    _ => continue,
+                };
+                let container = match containers.get(vertex) {
+                    Some(x) => Some(x.to_string()),
+                    None => self.moniker_container(vertex),
+                };
+                result.push(symbol(
+                    &tag.text,
+                    tag.kind,
+                    container,
+                    location,
+                    Some(range),
+                ));
+            }
+        }
+
+        // Full symbols usually repeat definitions, which already have stored ranges
+        fn visit_full(
+            symbols: Vec<lsp_types::DocumentSymbol>,
+            parent: Option<&str>,
+            found: &mut Vec<(lsp_types::DocumentSymbol, Option<String>)>,
+        ) {
+            for mut symbol in symbols {
+                let children = symbol.children.take().unwrap_or_default();
+                visit_full(children, Some(&symbol.name), found);
+                found.push((symbol, parent.map(|x| x.to_string())));
+            }
+        }
+        let mut found = Vec::new();
+        visit_full(full, None, &mut found);
+        for (x, container) in found {
+            if result.iter().any(|s| s.location.range == x.selection_range) {
+                continue;
+            }
+            let location = lsp_types::Location {
+                uri: location.clone(),
+                range: x.selection_range,
+            };
+            result.push(symbol(&x.name, x.kind, container, location, None));
+        }
+        result
+    }
+
+    /// Container named by the moniker of the range, like `module` for `module:Symbol`
+    fn moniker_container(&self, vertex: &lsif::Id) -> Option<String> {
+        let mut visited = std::collections::HashSet::new();
+        let mut queue = vec![vertex.clone()];
+        while let Some(vertex) = queue.pop() {
+            if !visited.insert(vertex.clone()) {
+                continue;
+            }
+            let edges = self.graph.outgoing_from(&vertex).into_par_iter();
+            for edge in edges.collect::<Vec<_>>() {
+                match edge.edge() {
+                    lsif::Edge::Next(x) => queue.push(x.in_v.clone()),
+                    lsif::Edge::Moniker(x) => {
+                        if let Some(lsif::Vertex::Moniker(moniker)) =
+                            self.graph.vertex(&x.in_v).map(|x| x.vertex())
+                        {
+                            if let Some((container, _)) =
+                                moniker.identifier.rsplit_once(MONIKER_SEPARATORS)
+                            {
+                                let container = container.trim_end_matches(MONIKER_SEPARATORS);
+                                if !container.is_empty() {
+                                    return Some(container.to_string());
+                                }
+                            }
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+        None
+    }
+
+    /// Flattens result set chains of loaded ranges, so queries don't have to walk the graph.
+    ///
+    /// Ranges of a symbol share its result set, which is walked once and saved as one record,
+    /// and ranges are mapped to the record of the vertex their results start from.
+    fn navigation(&self) -> (Vec<Navigation>, Vec<NavigationRange>) {
+        let mut ranges = Vec::new();
+        self.ranges.scan(|k, v| ranges.push((k.clone(), v.id)));
+        let owners = ranges
+            .into_par_iter()
+            .map(|(vertex, range)| (self.navigation_owner(&vertex), range))
+            .collect::<Vec<_>>();
+        let unique = owners
+            .iter()
+            .map(|(owner, _)| owner.clone())
+            .collect::<std::collections::HashSet<_>>();
+        let records = unique
+            .into_par_iter()
+            .filter_map(|owner| {
+                let record = self.navigation_record(owner.clone());
+                (!record.is_empty()).then_some((owner, record))
+            })
+            .collect::<std::collections::HashMap<_, _>>();
+        let mapping = owners
+            .into_iter()
+            .filter_map(|(owner, range)| {
+                Some(NavigationRange {
+                    id: Id::new(),
+                    range,
+                    navigation: records.get(&owner)?.id,
+                    upload: self.upload,
+                })
+            })
+            .collect();
+        (records.into_values().collect(), mapping)
+    }
+
+    /// Vertex whose chain holds the results of a range: the range itself if it has results
+    /// of its own, otherwise the result set it continues to.
+    fn navigation_owner(&self, range: &lsif::Id) -> lsif::Id {
+        let edges = self.graph.outgoing_from(range).into_par_iter();
+        let mut next = None;
+        for edge in edges.collect::<Vec<_>>() {
+            match edge.edge() {
+                lsif::Edge::Next(x) => next = Some(x.in_v.clone()),
+                lsif::Edge::Hover(_)
+                | lsif::Edge::Definition(_)
+                | lsif::Edge::References(_)
+                | lsif::Edge::Implementation(_) => return range.clone(),
+                _ => {}
+            }
+        }
+        next.unwrap_or_else(|| range.clone())
+    }
+
+    /// Results of the chain starting at `vertex`.
+    fn navigation_record(&self, vertex: lsif::Id) -> Navigation {
+        let mut result = Navigation {
+            id: Id::new(),
+            upload: self.upload,
+            hover: None,
+            definitions: Vec::new(),
+            references: Vec::new(),
+            implementations: Vec::new(),
+        };
+        // Results closer to the start take precedence, as when walking the stored graph
+        let mut visited = std::collections::HashSet::new();
+        let mut queue = vec![vertex];
+        while let Some(vertex) = queue.pop() {
+            if !visited.insert(vertex.clone()) {
+                continue;
+            }
+            let edges = self.graph.outgoing_from(&vertex).into_par_iter();
+            for edge in edges.collect::<Vec<_>>() {
+                match edge.edge() {
+                    lsif::Edge::Next(x) => queue.push(x.in_v.clone()),
+                    lsif::Edge::Hover(x) if result.hover.is_none() => {
+                        result.hover = match self.graph.vertex(&x.in_v).map(|x| x.vertex()) {
+                            Some(lsif::Vertex::HoverResult { result }) => Some(result.clone()),
+                            _ => None,
+                        }
+                    }
+                    lsif::Edge::Definition(x) if result.definitions.is_empty() => {
+                        result.definitions = self.navigation_targets(&x.in_v)
+                    }
+                    lsif::Edge::References(x) if result.references.is_empty() => {
+                        result.references = self.navigation_targets(&x.in_v)
+                    }
+                    lsif::Edge::Implementation(x) if result.implementations.is_empty() => {
+                        result.implementations = self.navigation_targets(&x.in_v)
+                    }
+                    _ => {}
+                }
+            }
+        }
+        result
+    }
+
+    /// Loaded ranges which are items of the result vertex, including items of nested results.
+    fn navigation_targets(&self, result: &lsif::Id) -> Vec<NavigationTarget> {
+        let mut visited = std::collections::HashSet::new();
+        let mut queue = vec![result.clone()];
+        let mut targets = Vec::new();
+        while let Some(result) = queue.pop() {
+            if !visited.insert(result.clone()) {
+                continue;
+            }
+            let items = self.graph.outgoing_from(&result).into_par_iter();
+            for edge in items.collect::<Vec<_>>() {
+                let item = match edge.edge() {
+                    lsif::Edge::Item(x) => x,
+                    _ => continue,
+                };
+                match item.property {
+                    Some(
+                        lsif::ItemKind::ReferenceResults | lsif::ItemKind::ImplementationResults,
+                    ) => queue.extend(item.edge_data.in_vs.iter().cloned()),
+                    Some(lsif::ItemKind::ReferenceLinks | lsif::ItemKind::ImplementationLinks) => {}
+                    ref kind => targets.extend(item.edge_data.in_vs.iter().filter_map(|vertex| {
+                        Some(NavigationTarget {
+                            range: self.ranges.get(vertex)?.get().id,
+                            location: self.locations.get(vertex)?.get().clone(),
+                            kind: kind.clone(),
+                        })
+                    })),
+                }
+            }
+        }
+        targets
+    }
+
+    /// Finds the stored file of a document, `None` if the document is skipped
     #[instrument(level = Level::DEBUG, skip_all, ret, err, fields(doc_id = ?doc.entry().id, uri = doc.document().uri.to_string()))]
-    async fn load_doc(&self, doc: DocumentRef<'_>) -> eyre::Result<Option<Id<Vertex>>> {
+    async fn resolve_file(&self, doc: DocumentRef<'_>) -> eyre::Result<Option<FileWithPath>> {
         debug!("loading doc {:?}", doc.entry());
         let doc_id = doc.entry().id.clone();
         let doc = doc.document();
-        eyre::ensure!(doc.uri.scheme().to_ascii_lowercase() == "file");
+        if !self.schemes.iter().any(|x| x == doc.uri.scheme()) {
+            warn!("skipping document {} with unsupported scheme", doc.uri);
+            let mut report = self.report.lock().unwrap();
+            report.documents_skipped += 1;
+            report.warn(
+                doc.uri.as_str(),
+                format!("unsupported scheme {}", doc.uri.scheme()),
+            );
+            return Ok(None);
+        }
 
-        let path = doc.uri.path();
+        let key = uri_key(&doc.uri);
+        let path = key.as_str();
         let trie_node = match self.roots.get_ancestor(path) {
             Some(x) => x,
             None => {
                 warn!("no root found for a document {}", path);
+                let mut report = self.report.lock().unwrap();
+                report.documents_skipped += 1;
+                report.warn(doc.uri.as_str(), "no root found".to_string());
                 return Ok(None);
             }
         };
@@ -158,13 +589,14 @@ impl<'g, 's> Converter<'g, 's> {
         );
 
         let mut path = Vec::new();
-        let curr = match root {
-            Either::Left(id) => self.storage.get(id).await?,
-            Either::Right(id) => self.storage.get_by_oid(id).await?,
-        };
-        let mut curr = curr
-            .ok_or_eyre(eyre!("commit {} not found in DB", root))?
-            .root;
+        let commit = self
+            .storage
+            .get(root)
+            .await?
+            .ok_or_eyre(eyre!("commit {} not found in DB", root))?;
+        let location =
+            format!("bird:///{}/{}", commit.oid, suffix.trim_start_matches('/')).parse()?;
+        let mut curr = commit.root;
         for segment in suffix.split('/') {
             if segment.is_empty() {
                 continue;
@@ -191,30 +623,49 @@ impl<'g, 's> Converter<'g, 's> {
             .ok_or_eyre(eyre!("file {} not found in DB", root))?;
         path.push(node.id);
 
-        let file = match node {
-            Node {
-                content: FileContent::Text { .. },
-                ..
-            } => node,
+        let lines = match &node.content {
+            FileContent::Text { lines, .. } => lines,
             _ => return Err(eyre::eyre!("file {:?} is not a text document", curr.id)),
         };
-        let file = FileWithPath { node: file, path };
+        let texts = if self.encoding == encoding::CANONICAL {
+            std::collections::HashMap::new()
+        } else {
+            self.storage
+                .find::<Line>(doc! { "_id": { "$in": lines } }, None)
+                .await?
+                .into_iter()
+                .map(|x| (x.id, x.text))
+                .collect()
+        };
+        Ok(Some(FileWithPath {
+            id: doc_id,
+            document: without_contents(doc.clone()),
+            node,
+            path,
+            texts,
+            uri: doc.uri.clone(),
+            location,
+            commit: root,
+        }))
+    }
 
+    fn load_doc(&self, file: FileWithPath) -> eyre::Result<(lsif::Id, Id<Vertex>)> {
+        let doc_id = file.id.clone();
+        let document = file.document.clone();
         self.files
-            .insert_async(doc_id.clone(), file)
-            .await
+            .insert(doc_id.clone(), file)
             .expect("doc id is unique");
 
         let vertex_id = Id::new();
         self.vertices
-            .insert_async(
+            .insert(
                 doc_id.clone(),
                 Some(Vertex {
                     id: vertex_id,
-                    data: VertexInfo::Document(doc.clone()),
+                    data: VertexInfo::Document(document),
+                    upload: Some(self.upload),
                 }),
             )
-            .await
             .expect("doc_id is unique");
 
         self.graph
@@ -238,7 +689,7 @@ impl<'g, 's> Converter<'g, 's> {
             })
             .map(|(vertex, range)| self.load_range(&doc_id, vertex, range))
             .collect::<Result<Vec<_>, _>>()?;
-        Ok(Some(vertex_id))
+        Ok((doc_id, vertex_id))
     }
 
     #[instrument(skip(self), err)]
@@ -249,9 +700,120 @@ impl<'g, 's> Converter<'g, 's> {
             .into_par_iter()
             .map(|e| self.load_edge(vertex_id, e))
             .collect::<Result<Vec<_>, _>>()?;
+        self.graph
+            .outgoing_from(doc_id)
+            .into_par_iter()
+            .flat_map_iter(|edge| match edge.edge() {
+                lsif::Edge::FoldingRange(x) | lsif::Edge::DocumentSymbol(x) => {
+                    std::slice::from_ref(&x.in_v)
+                }
+                lsif::Edge::Contains(x) => x.in_vs.as_slice(),
+                _ => &[],
+            })
+            .for_each(|result| self.normalize_result(doc_id, result));
         Ok(())
     }
 
+    /// Folding ranges, document symbols and full ranges of range tags are stored with positions
+    /// as is, so their characters are converted to the canonical encoding here
+    fn normalize_result(&self, doc_id: &lsif::Id, result: &lsif::Id) {
+        if self.encoding == encoding::CANONICAL {
+            return;
+        }
+        let file = match self.files.get(doc_id) {
+            Some(x) => x,
+            None => return,
+        };
+        let FileWithPath { node, texts, .. } = file.get();
+        let lines = match &node.content {
+            FileContent::Text { lines, .. } => lines,
+            _ => return,
+        };
+        let normalize =
+            |line: u32, offset: u32| match lines.get(line as usize).and_then(|x| texts.get(x)) {
+                Some(text) => encoding::convert(text, offset, self.encoding, encoding::CANONICAL),
+                None => offset,
+            };
+        let mut vertex = match self.vertices.get(result) {
+            Some(x) => x,
+            None => return,
+        };
+        match vertex.get_mut() {
+            Some(Vertex {
+                data: VertexInfo::FoldingRangeResult { result },
+                ..
+            }) => {
+                for range in result {
+                    range.start_character = range
+                        .start_character
+                        .map(|x| normalize(range.start_line, x));
+                    range.end_character = range.end_character.map(|x| normalize(range.end_line, x));
+                }
+            }
+            Some(Vertex {
+                data:
+                    VertexInfo::DocumentSymbolResult {
+                        result: DocumentSymbolOrRangeBasedVec::DocumentSymbol(symbols),
+                    },
+                ..
+            }) => {
+                fn visit(symbols: &mut [lsp_types::DocumentSymbol], f: &impl Fn(u32, u32) -> u32) {
+                    for symbol in symbols {
+                        for range in [&mut symbol.range, &mut symbol.selection_range] {
+                            for pos in [&mut range.start, &mut range.end] {
+                                pos.character = f(pos.line, pos.character);
+                            }
+                        }
+                        visit(symbol.children.as_deref_mut().unwrap_or_default(), f);
+                    }
+                }
+                visit(symbols, &normalize)
+            }
+            Some(Vertex {
+                data:
+                    VertexInfo::Range {
+                        tag:
+                            Some(
+                                lsif::RangeTag::Definition(lsif::DefinitionTag {
+                                    full_range, ..
+                                })
+                                | lsif::RangeTag::Declaration(lsif::DeclarationTag {
+                                    full_range,
+                                    ..
+                                }),
+                            ),
+                        ..
+                    },
+                ..
+            }) => {
+                for pos in [&mut full_range.start, &mut full_range.end] {
+                    pos.character = normalize(pos.line, pos.character);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Replaces ids of range vertices in range-based symbols with ids of the stored ranges.
+    /// Children of symbols whose ranges are not loaded take their place.
+    fn document_symbols(
+        &self,
+        symbols: Vec<lsif::RangeBasedDocumentSymbol>,
+    ) -> Vec<RangeBasedDocumentSymbol> {
+        let mut result = Vec::new();
+        for symbol in symbols {
+            let children = self.document_symbols(symbol.children);
+            match self.ranges.get(&symbol.id) {
+                Some(range) => result.push(RangeBasedDocumentSymbol {
+                    range: range.get().id,
+                    children,
+                }),
+                None => result.extend(children),
+            }
+        }
+        result
+    }
+
     #[instrument(level = Level::DEBUG, skip_all, ret, err, fields(out_v = %out_v, edge_id = ?edge.entry().id))]
     fn load_edge(&self, out_v: Id<Vertex>, edge: EdgeRef<'_>) -> eyre::Result<Option<Id<Edge>>> {
         let id = {
@@ -293,6 +855,7 @@ impl<'g, 's> Converter<'g, 's> {
             .entry(edge.entry().id.clone())
             .insert_entry(Either::Right(Edge {
                 id,
+                upload: Some(self.upload),
                 data: match edge.edge() {
                     lsif::Edge::Contains(_x) => EdgeInfo::Contains(edge_data_multi),
                     lsif::Edge::Moniker(_x) => EdgeInfo::Moniker(edge_data),
@@ -327,6 +890,7 @@ impl<'g, 's> Converter<'g, 's> {
                     lsif::Edge::DocumentLink(_x) => EdgeInfo::DocumentLink(edge_data),
                     lsif::Edge::DocumentSymbol(_x) => EdgeInfo::DocumentSymbol(edge_data),
                     lsif::Edge::Diagnostic(_x) => EdgeInfo::Diagnostic(edge_data),
+                    lsif::Edge::Attach(_x) => EdgeInfo::Attach(edge_data),
                 },
             }));
 
@@ -367,7 +931,7 @@ impl<'g, 's> Converter<'g, 's> {
         let data = match vertex.vertex().clone() {
             lsif::Vertex::MetaData(x) => VertexInfo::MetaData(x),
             lsif::Vertex::Project(x) => VertexInfo::Project(x),
-            lsif::Vertex::Document(x) => VertexInfo::Document(x),
+            lsif::Vertex::Document(x) => VertexInfo::Document(without_contents(x)),
             lsif::Vertex::Range { tag, .. } => {
                 let range = match self.ranges.get(&vertex.entry().id) {
                     Some(x) => x,
@@ -387,7 +951,9 @@ impl<'g, 's> Converter<'g, 's> {
             lsif::Vertex::ResultSet(x) => VertexInfo::ResultSet(x),
             lsif::Vertex::Moniker(x) => VertexInfo::Moniker(x),
             lsif::Vertex::PackageInformation(x) => VertexInfo::PackageInformation(x),
-            lsif::Vertex::Event(_) => return Ok(None),
+            lsif::Vertex::Event(_) | lsif::Vertex::Source(_) | lsif::Vertex::Capabilities(_) => {
+                return Ok(None)
+            }
             lsif::Vertex::DefinitionResult => VertexInfo::DefinitionResult {},
             lsif::Vertex::DeclarationResult => VertexInfo::DeclarationResult {},
             lsif::Vertex::TypeDefinitionResult => VertexInfo::TypeDefinitionResult {},
@@ -397,18 +963,31 @@ impl<'g, 's> Converter<'g, 's> {
                 VertexInfo::FoldingRangeResult { result }
             }
             lsif::Vertex::HoverResult { result } => VertexInfo::HoverResult { result },
-            lsif::Vertex::DocumentSymbolResult { result } => {
-                VertexInfo::DocumentSymbolResult { result }
-            }
-            lsif::Vertex::DocumentLinkResult { result } => VertexInfo::DocumentLinkResult {
-                result: result.into_iter().map(|_x| todo!()).collect(),
-            },
-            lsif::Vertex::DiagnosticResult { result } => VertexInfo::DiagnosticResult {
-                result: result.into_iter().map(|_x| todo!()).collect(),
+            lsif::Vertex::DocumentSymbolResult { result } => VertexInfo::DocumentSymbolResult {
+                result: match result {
+                    lsif::DocumentSymbolOrRangeBasedVec::DocumentSymbol(x) => {
+                        DocumentSymbolOrRangeBasedVec::DocumentSymbol(x)
+                    }
+                    lsif::DocumentSymbolOrRangeBasedVec::RangeBased(x) => {
+                        DocumentSymbolOrRangeBasedVec::RangeBased(self.document_symbols(x))
+                    }
+                },
             },
+            // Their positions are not ranges of the dump, so they can't be stored as is
+            lsif::Vertex::DocumentLinkResult { .. } | lsif::Vertex::DiagnosticResult { .. } => {
+                warn!(
+                    "document links and diagnostics are not supported, skipping {:?}",
+                    vertex.entry()
+                );
+                return Ok(None);
+            }
         };
         let id = Id::new();
-        entry.insert(Some(Vertex { id, data }));
+        entry.insert(Some(Vertex {
+            id,
+            data,
+            upload: Some(self.upload),
+        }));
         Ok(Some(id))
     }
 
@@ -418,30 +997,73 @@ impl<'g, 's> Converter<'g, 's> {
         doc_id: &lsif::Id,
         vertex: VertexRef<'_>,
         range: &lsp_types::Range,
-    ) -> eyre::Result<Id<Range>> {
+    ) -> eyre::Result<Option<Id<Range>>> {
         let entry = self.ranges.entry(vertex.entry().id.clone());
         let entry = match entry {
-            Entry::Occupied(existing) => return Ok(existing.get().id()),
+            Entry::Occupied(existing) => return Ok(Some(existing.get().id())),
             Entry::Vacant(vacant) => vacant,
         };
         trace!("loading range {:?}", range);
 
-        let (line_id, path) = match self.files.get(doc_id) {
+        let (start_line, start, end_line, end, path, location) = match self.files.get(doc_id) {
             Some(x) => {
-                let FileWithPath { node, path } = x.get();
-                match &node.content {
-                    FileContent::Text { lines, .. } => match lines.get(range.start.line as usize) {
-                        Some(x) => (*x, path.clone()),
-                        None => {
-                            return Err(eyre::eyre!(
-                                "document {:?} is not long enough to get line #{}",
-                                doc_id,
-                                range.start.line
-                            ))
-                        }
-                    },
+                let FileWithPath {
+                    node,
+                    path,
+                    texts,
+                    uri,
+                    location,
+                    ..
+                } = x.get();
+                let lines = match &node.content {
+                    FileContent::Text { lines, .. } => lines,
                     _ => return Err(eyre::eyre!("document {:?} is not a text document", doc_id)),
-                }
+                };
+                let start_line = match lines.get(range.start.line as usize) {
+                    Some(x) => *x,
+                    None => {
+                        warn!(
+                            "document {:?} is not long enough to get line #{}",
+                            doc_id, range.start.line
+                        );
+                        let mut report = self.report.lock().unwrap();
+                        report.ranges_unmapped += 1;
+                        report.warn(
+                            uri.as_str(),
+                            format!(
+                                "range {:?} starts at line {}, but the file has {} lines",
+                                vertex.entry().id,
+                                range.start.line,
+                                lines.len()
+                            ),
+                        );
+                        return Ok(None);
+                    }
+                };
+                // Ranges may end right after the last line, so clamp them to the end of the file
+                let (end_line, end, end_line_no) = match lines.get(range.end.line as usize) {
+                    Some(x) => (*x, range.end.character, range.end.line),
+                    None => match lines.last() {
+                        Some(x) => (*x, u32::MAX, lines.len() as u32 - 1),
+                        None => (start_line, u32::MAX, range.start.line),
+                    },
+                };
+                let normalize = |line: Id<Line>, offset: u32| match texts.get(&line) {
+                    Some(text) => {
+                        encoding::convert(text, offset, self.encoding, encoding::CANONICAL)
+                    }
+                    None => offset,
+                };
+                let start = normalize(start_line, range.start.character);
+                let end = normalize(end_line, end);
+                let location = lsp_types::Location {
+                    uri: location.clone(),
+                    range: lsp_types::Range {
+                        start: lsp_types::Position::new(range.start.line, start),
+                        end: lsp_types::Position::new(end_line_no, end),
+                    },
+                };
+                (start_line, start, end_line, end, path.clone(), location)
             }
             None => {
                 return Err(eyre::eyre!(
@@ -452,21 +1074,26 @@ impl<'g, 's> Converter<'g, 's> {
             }
         };
 
-        let end = if range.end.line == range.start.line {
-            range.end.character
-        } else {
-            u32::MAX
-        };
-
         let id = Id::new();
         let range = Range {
             id,
-            line_id,
-            start: range.start.character,
+            start_line,
+            end_line,
+            start,
             end,
             path,
+            upload: Some(self.upload),
         };
         entry.insert_entry(range);
-        Ok(id)
+        let _ = self.locations.insert(vertex.entry().id.clone(), location);
+        Ok(Some(id))
+    }
+}
+
+/// Contents of documents are already stored as files of the commit, so they are not kept in the graph
+fn without_contents(document: lsif::Document) -> lsif::Document {
+    lsif::Document {
+        contents: None,
+        ..document
     }
 }
@@ -0,0 +1,406 @@
+use std::collections::HashMap;
+use std::io::Write;
+
+use bson::doc;
+use either::Either;
+use eyre::{eyre, OptionExt};
+use gix::ObjectId;
+use lsp_types::lsif::{self, Element, Entry};
+use lsp_types::{Position, Url};
+use radix_trie::{Trie, TrieCommon};
+use shatterbird_storage::model::lang::{
+    DocumentSymbolOrRangeBasedVec, EdgeData, EdgeDataMultiIn, EdgeInfo, Item,
+    RangeBasedDocumentSymbol, VertexInfo,
+};
+use shatterbird_storage::model::{Commit, Edge, FileContent, Line, Node, Range, Upload, Vertex};
+use shatterbird_storage::util::graph::line_span;
+use shatterbird_storage::util::{encoding, uploads};
+use shatterbird_storage::{Id, Storage};
+use tracing::{info, instrument, warn};
+
+use super::roots::{prefix_key, uri_key};
+
+/// Writes uploads of a commit (or a single upload) as an LSIF dump in JSON lines format.
+///
+/// Document URIs become `bird:///<commit>/<path>` unless `uri_prefix` is given,
+/// in which case they are `<uri_prefix><path>`.
+#[instrument(skip(storage, out), err)]
+pub async fn export_lsif(
+    storage: &Storage,
+    source: Either<ObjectId, Id<Upload>>,
+    uri_prefix: Option<Url>,
+    out: impl Write,
+) -> eyre::Result<()> {
+    let uploads = match source {
+        Either::Left(oid) => {
+            let commit: Commit = storage
+                .get_by_oid(oid)
+                .await?
+                .ok_or_eyre(eyre!("commit {} not found in DB", oid))?;
+            storage
+                .find::<Upload>(
+                    doc! {
+                        "commits": commit.id,
+                        "_id": { "$in": uploads::active(storage).await? },
+                    },
+                    None,
+                )
+                .await?
+        }
+        Either::Right(id) => vec![storage
+            .get(id)
+            .await?
+            .ok_or_eyre(eyre!("upload {} not found", id))?],
+    };
+    if uploads.is_empty() {
+        return Err(eyre!("no uploads found for {:?}", source));
+    }
+    let ids = uploads.iter().map(|x| x.id).collect::<Vec<_>>();
+    info!("exporting {} uploads", ids.len());
+
+    let mut roots = Trie::new();
+    for upload in &uploads {
+        for root in &upload.roots {
+            let commit: Commit = storage
+                .get(root.commit)
+                .await?
+                .ok_or_eyre(eyre!("commit {} not found in DB", root.commit))?;
+            roots.insert(prefix_key(&root.prefix), commit.oid);
+        }
+    }
+
+    let filter = doc! { "upload": { "$in": &ids } };
+    let vertices = storage.find::<Vertex>(filter.clone(), None).await?;
+    let edges = storage.find::<Edge>(filter.clone(), None).await?;
+    let ranges = storage.find::<Range>(filter, None).await?;
+    let positions = range_positions(storage, &ranges).await?;
+    write_lsif(out, roots, uri_prefix, positions, vertices, edges)
+}
+
+/// Writes stored vertices and edges as an LSIF dump, given positions of their ranges
+/// and commits of the roots documents are stored under.
+pub(super) fn write_lsif(
+    out: impl Write,
+    roots: Trie<String, ObjectId>,
+    uri_prefix: Option<Url>,
+    positions: HashMap<Id<Range>, lsp_types::Range>,
+    vertices: Vec<Vertex>,
+    edges: Vec<Edge>,
+) -> eyre::Result<()> {
+    let mut exporter = Exporter {
+        out,
+        roots,
+        uri_prefix,
+        positions,
+        next_id: 0,
+        vertices: HashMap::new(),
+        ranges: HashMap::new(),
+    };
+    exporter.export(vertices, edges)?;
+    exporter.out.flush()?;
+    Ok(())
+}
+
+/// Recomputes LSP positions of stored ranges from line numbers of their files.
+async fn range_positions(
+    storage: &Storage,
+    ranges: &[Range],
+) -> eyre::Result<HashMap<Id<Range>, lsp_types::Range>> {
+    let files = ranges
+        .iter()
+        .filter_map(|x| x.path.last().copied())
+        .collect::<Vec<_>>();
+    let files = storage
+        .find::<Node>(doc! { "_id": { "$in": files } }, None)
+        .await?
+        .into_iter()
+        .filter_map(|x| match x.content {
+            FileContent::Text { lines, .. } => Some((x.id, lines)),
+            _ => None,
+        })
+        .collect::<HashMap<_, _>>();
+
+    // Ranges ending past the last line are clamped to its end on import
+    let clamped = ranges
+        .iter()
+        .filter(|x| x.end == u32::MAX)
+        .map(|x| x.end_line)
+        .collect::<Vec<_>>();
+    let lengths = storage
+        .find::<Line>(doc! { "_id": { "$in": clamped } }, None)
+        .await?
+        .into_iter()
+        .map(|x| (x.id, x.text.encode_utf16().count() as u32))
+        .collect::<HashMap<_, _>>();
+    Ok(positions(ranges, &files, &lengths))
+}
+
+/// Positions of ranges in files with the given lines, and lengths of lines the clamped ranges end at
+pub(super) fn positions(
+    ranges: &[Range],
+    files: &HashMap<Id<Node>, Vec<Id<Line>>>,
+    lengths: &HashMap<Id<Line>, u32>,
+) -> HashMap<Id<Range>, lsp_types::Range> {
+    let mut result = HashMap::new();
+    for range in ranges {
+        let lines = match range.path.last().and_then(|x| files.get(x)) {
+            Some(x) => x,
+            None => {
+                warn!("file of range {} is not a stored text file", range.id);
+                continue;
+            }
+        };
+        let (start, end) = match line_span(lines, range) {
+            Some(x) => x,
+            None => {
+                warn!("lines of range {} not found in its file", range.id);
+                continue;
+            }
+        };
+        let end_character = match range.end {
+            u32::MAX => lengths.get(&range.end_line).copied().unwrap_or_default(),
+            x => x,
+        };
+        result.insert(
+            range.id,
+            lsp_types::Range {
+                start: Position::new(start as _, range.start),
+                end: Position::new(end as _, end_character),
+            },
+        );
+    }
+    result
+}
+
+struct Exporter<W> {
+    out: W,
+    roots: Trie<String, ObjectId>,
+    uri_prefix: Option<Url>,
+    positions: HashMap<Id<Range>, lsp_types::Range>,
+    next_id: i32,
+    vertices: HashMap<Id<Vertex>, lsif::Id>,
+    ranges: HashMap<Id<Range>, lsif::Id>,
+}
+
+impl<W: Write> Exporter<W> {
+    fn export(&mut self, vertices: Vec<Vertex>, edges: Vec<Edge>) -> eyre::Result<()> {
+        // metaData must be the first entry of a dump, and only one is allowed
+        let metadata = vertices.iter().find_map(|x| match &x.data {
+            VertexInfo::MetaData(meta) => Some(meta.clone()),
+            _ => None,
+        });
+        let project_root = match (&self.uri_prefix, self.roots.iter().next()) {
+            (Some(prefix), _) => prefix.clone(),
+            (None, Some((_, oid))) => format!("bird:///{}/", oid).parse()?,
+            (None, None) => "bird:///".parse()?,
+        };
+        let metadata = lsif::MetaData {
+            version: "0.6.0".to_string(),
+            project_root: None,
+            // Ranges are stored in the canonical encoding
+            position_encoding: encoding::CANONICAL,
+            tool_info: metadata.and_then(|x| x.tool_info),
+        };
+        self.emit(Element::Vertex(lsif::Vertex::MetaData(metadata)))?;
+        self.emit(Element::Vertex(lsif::Vertex::Source(lsif::Source {
+            workspace_root: Some(project_root),
+            repository: None,
+        })))?;
+
+        // Range-based document symbols refer to ranges, so those have to be emitted first
+        let mut vertices = vertices;
+        vertices.sort_by_key(|x| matches!(x.data, VertexInfo::DocumentSymbolResult { .. }));
+        let mut exported = 0;
+        for vertex in vertices {
+            let range = match &vertex.data {
+                VertexInfo::Range { range, .. } => Some(*range),
+                _ => None,
+            };
+            let data = match self.convert_vertex(vertex.data)? {
+                Some(x) => x,
+                None => continue,
+            };
+            let id = self.emit(Element::Vertex(data))?;
+            if let Some(range) = range {
+                self.ranges.insert(range, id.clone());
+            }
+            self.vertices.insert(vertex.id, id);
+            exported += 1;
+        }
+
+        let total = edges.len();
+        let mut skipped = 0;
+        for edge in edges {
+            match self.convert_edge(&edge.data) {
+                Some(data) => {
+                    self.emit(Element::Edge(data))?;
+                }
+                None => skipped += 1,
+            }
+        }
+        if skipped > 0 {
+            warn!(
+                "skipped {} edges referencing vertices which are not exported",
+                skipped
+            );
+        }
+        info!(
+            "exported {} vertices and {} edges",
+            exported,
+            total - skipped
+        );
+        Ok(())
+    }
+
+    fn emit(&mut self, data: Element) -> eyre::Result<lsif::Id> {
+        self.next_id += 1;
+        let id = lsif::Id::Number(self.next_id);
+        serde_json::to_writer(
+            &mut self.out,
+            &Entry {
+                id: id.clone(),
+                data,
+            },
+        )?;
+        self.out.write_all(b"\n")?;
+        Ok(id)
+    }
+
+    fn convert_vertex(&self, data: VertexInfo) -> eyre::Result<Option<lsif::Vertex>> {
+        Ok(Some(match data {
+            VertexInfo::MetaData(_) => return Ok(None),
+            VertexInfo::Project(x) => lsif::Vertex::Project(x),
+            VertexInfo::Document(mut x) => {
+                x.uri = match self.rewrite_uri(&x.uri)? {
+                    Some(uri) => uri,
+                    None => {
+                        warn!("document {} is not under any root, skipping", x.uri);
+                        return Ok(None);
+                    }
+                };
+                lsif::Vertex::Document(x)
+            }
+            VertexInfo::Range { range, tag } => match self.positions.get(&range) {
+                Some(&range) => lsif::Vertex::Range { range, tag },
+                None => return Ok(None),
+            },
+            VertexInfo::ResultSet(x) => lsif::Vertex::ResultSet(x),
+            VertexInfo::Moniker(x) => lsif::Vertex::Moniker(x),
+            VertexInfo::PackageInformation(x) => lsif::Vertex::PackageInformation(x),
+            VertexInfo::DefinitionResult {} => lsif::Vertex::DefinitionResult,
+            VertexInfo::DeclarationResult {} => lsif::Vertex::DeclarationResult,
+            VertexInfo::TypeDefinitionResult {} => lsif::Vertex::TypeDefinitionResult,
+            VertexInfo::ReferenceResult {} => lsif::Vertex::ReferenceResult,
+            VertexInfo::ImplementationResult {} => lsif::Vertex::ImplementationResult,
+            VertexInfo::FoldingRangeResult { result } => {
+                lsif::Vertex::FoldingRangeResult { result }
+            }
+            VertexInfo::HoverResult { result } => lsif::Vertex::HoverResult { result },
+            VertexInfo::DocumentSymbolResult { result } => lsif::Vertex::DocumentSymbolResult {
+                result: match result {
+                    DocumentSymbolOrRangeBasedVec::DocumentSymbol(x) => {
+                        lsif::DocumentSymbolOrRangeBasedVec::DocumentSymbol(x)
+                    }
+                    DocumentSymbolOrRangeBasedVec::RangeBased(x) => {
+                        lsif::DocumentSymbolOrRangeBasedVec::RangeBased(self.document_symbols(x))
+                    }
+                },
+            },
+            VertexInfo::DocumentLinkResult { .. } | VertexInfo::DiagnosticResult { .. } => {
+                warn!("document links and diagnostics are not supported, skipping");
+                return Ok(None);
+            }
+        }))
+    }
+
+    /// Symbols whose ranges are not exported are replaced with their children
+    fn document_symbols(
+        &self,
+        symbols: Vec<RangeBasedDocumentSymbol>,
+    ) -> Vec<lsif::RangeBasedDocumentSymbol> {
+        let mut result = Vec::new();
+        for symbol in symbols {
+            let children = self.document_symbols(symbol.children);
+            match self.ranges.get(&symbol.range) {
+                Some(id) => result.push(lsif::RangeBasedDocumentSymbol {
+                    id: id.clone(),
+                    children,
+                }),
+                None => result.extend(children),
+            }
+        }
+        result
+    }
+
+    fn convert_edge(&self, data: &EdgeInfo) -> Option<lsif::Edge> {
+        let single = |x: &EdgeData| {
+            Some(lsif::EdgeData {
+                in_v: self.vertices.get(&x.in_v)?.clone(),
+                out_v: self.vertices.get(&x.out_v)?.clone(),
+            })
+        };
+        // Incoming vertices which were not exported are dropped, but not the whole edge
+        let multi = |x: &EdgeDataMultiIn| {
+            let in_vs = x
+                .in_vs
+                .iter()
+                .filter_map(|v| self.vertices.get(v).cloned())
+                .collect::<Vec<_>>();
+            if in_vs.is_empty() {
+                return None;
+            }
+            Some(lsif::EdgeDataMultiIn {
+                in_vs,
+                out_v: self.vertices.get(&x.out_v)?.clone(),
+            })
+        };
+        Some(match data {
+            EdgeInfo::Contains(x) => lsif::Edge::Contains(multi(x)?),
+            EdgeInfo::Moniker(x) => lsif::Edge::Moniker(single(x)?),
+            EdgeInfo::NextMoniker(x) => lsif::Edge::NextMoniker(single(x)?),
+            EdgeInfo::Next(x) => lsif::Edge::Next(single(x)?),
+            EdgeInfo::PackageInformation(x) => lsif::Edge::PackageInformation(single(x)?),
+            EdgeInfo::Item(Item {
+                document,
+                property,
+                edge_data,
+            }) => lsif::Edge::Item(lsif::Item {
+                document: self.vertices.get(document)?.clone(),
+                property: property.clone(),
+                edge_data: multi(edge_data)?,
+            }),
+            EdgeInfo::Definition(x) => lsif::Edge::Definition(single(x)?),
+            EdgeInfo::Declaration(x) => lsif::Edge::Declaration(single(x)?),
+            EdgeInfo::Hover(x) => lsif::Edge::Hover(single(x)?),
+            EdgeInfo::References(x) => lsif::Edge::References(single(x)?),
+            EdgeInfo::Implementation(x) => lsif::Edge::Implementation(single(x)?),
+            EdgeInfo::TypeDefinition(x) => lsif::Edge::TypeDefinition(single(x)?),
+            EdgeInfo::FoldingRange(x) => lsif::Edge::FoldingRange(single(x)?),
+            EdgeInfo::DocumentLink(x) => lsif::Edge::DocumentLink(single(x)?),
+            EdgeInfo::DocumentSymbol(x) => lsif::Edge::DocumentSymbol(single(x)?),
+            EdgeInfo::Diagnostic(x) => lsif::Edge::Diagnostic(single(x)?),
+            EdgeInfo::Attach(x) => lsif::Edge::Attach(single(x)?),
+        })
+    }
+
+    fn rewrite_uri(&self, uri: &Url) -> eyre::Result<Option<Url>> {
+        let key = uri_key(uri);
+        let path = key.as_str();
+        let node = match self.roots.get_ancestor(path) {
+            Some(x) => x,
+            None => return Ok(None),
+        };
+        let prefix = node
+            .key()
+            .expect("trie key is present when trie node is found");
+        let oid = node
+            .value()
+            .expect("trie value is present when trie node is found");
+        let relative = path[prefix.len()..].trim_start_matches('/');
+        let uri = match &self.uri_prefix {
+            Some(base) => format!("{}{}", base, relative).parse()?,
+            None => format!("bird:///{}/{}", oid, relative).parse()?,
+        };
+        Ok(Some(uri))
+    }
+}
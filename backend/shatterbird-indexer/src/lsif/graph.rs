@@ -5,7 +5,8 @@ use multimap::MultiMap;
 use rayon::prelude::*;
 
 use crate::exclusive::Exclusive;
-use lsp_types::lsif::{Document, Edge, Element, Entry, Id, Vertex};
+use lsp_types::lsif::{Document, Edge, Element, Entry, Id, MetaData, Project, Source, Vertex};
+use lsp_types::Url;
 
 use super::lsif_ext::EdgeExtensions;
 
@@ -46,8 +47,13 @@ entry_ref!(EdgeRef, edge -> Edge, Element::Edge(e) => e);
 pub struct Graph<'a> {
     arena: Exclusive<&'a Bump>,
     vertices: HashMap<Id, VertexRef<'a>>,
+    metadata: Option<&'a MetaData>,
+    source: Option<&'a Source>,
+    projects: Vec<&'a Project>,
+    events: usize,
     documents: Vec<DocumentRef<'a>>,
     outgoing: MultiMap<Id, EdgeRef<'a>>,
+    attachments: Vec<EdgeRef<'a>>,
 }
 
 impl<'a> Graph<'a> {
@@ -55,8 +61,13 @@ impl<'a> Graph<'a> {
         Graph {
             arena: Exclusive::new(arena),
             vertices: HashMap::new(),
+            metadata: None,
+            source: None,
+            projects: Vec::new(),
+            events: 0,
             documents: Vec::new(),
             outgoing: MultiMap::new(),
+            attachments: Vec::new(),
         }
     }
 
@@ -71,12 +82,20 @@ impl<'a> Graph<'a> {
         };
         match &entry.data {
             Element::Vertex(v) => {
-                if let Vertex::Document(_) = v {
-                    self.documents.push(DocumentRef::new(entry).unwrap());
+                match v {
+                    Vertex::Document(_) => self.documents.push(DocumentRef::new(entry).unwrap()),
+                    Vertex::MetaData(data) => self.metadata = Some(data),
+                    Vertex::Source(data) => self.source = Some(data),
+                    Vertex::Project(project) => self.projects.push(project),
+                    Vertex::Event(_) => self.events += 1,
+                    _ => {}
                 }
                 self.vertices.insert(id, VertexRef::new(entry).unwrap());
             }
             Element::Edge(e) => {
+                if let Edge::Attach(_) = e {
+                    self.attachments.push(EdgeRef::new(entry).unwrap());
+                }
                 // out_v -> { in_vs }
                 e.edge_data().each().for_each(|edge| {
                     self.outgoing
@@ -86,6 +105,29 @@ impl<'a> Graph<'a> {
         }
     }
 
+    pub fn metadata(&self) -> Option<&'a MetaData> {
+        self.metadata
+    }
+
+    /// Root of the indexed workspace, `projectRoot` of metaData is deprecated since LSIF 0.6
+    pub fn project_root(&self) -> Option<&'a Url> {
+        self.source
+            .and_then(|x| x.workspace_root.as_ref())
+            .or_else(|| self.metadata.and_then(|x| x.project_root.as_ref()))
+    }
+
+    pub fn attachments(&self) -> &[EdgeRef<'a>] {
+        &self.attachments
+    }
+
+    pub fn projects(&self) -> &[&'a Project] {
+        &self.projects
+    }
+
+    pub fn events(&self) -> usize {
+        self.events
+    }
+
     pub fn vertex(&self, id: &Id) -> Option<VertexRef<'a>> {
         self.vertices.get(id).copied()
     }
@@ -101,8 +143,4 @@ impl<'a> Graph<'a> {
     pub fn documents(&self) -> impl IntoParallelIterator<Item = DocumentRef<'_>> {
         self.documents.par_iter().copied()
     }
-
-    pub fn vertices(&self) -> impl IntoParallelIterator<Item = (&'_ Id, VertexRef<'_>)> {
-        self.vertices.par_iter().map(|(k, v)| (k, *v))
-    }
 }
@@ -0,0 +1,72 @@
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+
+use eyre::{eyre, WrapErr};
+use flate2::bufread::MultiGzDecoder;
+use lsp_types::lsif::Entry;
+
+const GZIP_MAGIC: &[u8] = &[0x1f, 0x8b];
+const ZSTD_MAGIC: &[u8] = &[0x28, 0xb5, 0x2f, 0xfd];
+
+/// Reads LSIF entries from a file (or stdin, if path is `-`).
+///
+/// Gzip and zstd compression are detected by magic bytes, and the content may be either
+/// a stream of JSON objects (usually one per line) or a single JSON array.
+pub fn read(path: &Path) -> eyre::Result<Box<dyn Iterator<Item = eyre::Result<Entry>>>> {
+    let reader: Box<dyn BufRead> = match path.as_os_str().as_encoded_bytes() {
+        b"-" => Box::new(BufReader::new(std::io::stdin())),
+        _ => {
+            Box::new(BufReader::new(File::open(path).wrap_err_with(|| {
+                format!("failed to open {}", path.display())
+            })?))
+        }
+    };
+    let mut reader = decompress(reader)?;
+
+    let display = path.display().to_string();
+    if first_non_whitespace(&mut reader)? == Some(b'[') {
+        let entries: Vec<Entry> = serde_json::from_reader(reader)
+            .map_err(|e| eyre!("failed to parse {}: {}", display, e))?;
+        return Ok(Box::new(entries.into_iter().map(Ok)));
+    }
+
+    let entries = serde_json::Deserializer::from_reader(reader)
+        .into_iter::<Entry>()
+        .map(move |entry| entry.map_err(|e| eyre!("failed to parse {}: {}", display, e)));
+    Ok(Box::new(entries))
+}
+
+fn decompress(mut reader: Box<dyn BufRead>) -> eyre::Result<Box<dyn BufRead>> {
+    let head = reader.fill_buf()?;
+    if head.starts_with(GZIP_MAGIC) {
+        Ok(Box::new(BufReader::new(MultiGzDecoder::new(reader))))
+    } else if head.starts_with(ZSTD_MAGIC) {
+        Ok(Box::new(BufReader::new(zstd::Decoder::with_buffer(
+            reader,
+        )?)))
+    } else {
+        Ok(reader)
+    }
+}
+
+/// Skips leading whitespace and returns the next byte without consuming it.
+fn first_non_whitespace(reader: &mut impl BufRead) -> eyre::Result<Option<u8>> {
+    loop {
+        let buf = reader.fill_buf()?;
+        if buf.is_empty() {
+            return Ok(None);
+        }
+        match buf.iter().position(|b| !b.is_ascii_whitespace()) {
+            Some(pos) => {
+                let byte = buf[pos];
+                reader.consume(pos);
+                return Ok(Some(byte));
+            }
+            None => {
+                let len = buf.len();
+                reader.consume(len);
+            }
+        }
+    }
+}
@@ -1,5 +1,8 @@
 use either::Either;
-use lsp_types::lsif::{Edge, EdgeData, EdgeDataMultiIn, Id};
+use lsp_types::lsif::{
+    DocumentSymbolOrRangeBasedVec, Edge, EdgeData, EdgeDataMultiIn, Element, Entry, Id,
+    RangeBasedDocumentSymbol, Vertex,
+};
 use rayon::iter::{IndexedParallelIterator, IntoParallelRefIterator, ParallelIterator};
 use std::iter;
 
@@ -40,11 +43,14 @@ impl<'a> EitherEdgeData<'a> {
 }
 
 pub trait EdgeExtensions {
-    fn edge_data(&self) -> EitherEdgeData;
+    fn edge_data(&self) -> EitherEdgeData<'_>;
+
+    /// Label of the edge as written in the dump.
+    fn label(&self) -> &'static str;
 }
 
 impl EdgeExtensions for Edge {
-    fn edge_data(&self) -> EitherEdgeData {
+    fn edge_data(&self) -> EitherEdgeData<'_> {
         match self {
             Edge::Contains(x) | Edge::Item(lsp_types::lsif::Item { edge_data: x, .. }) => {
                 EitherEdgeData::Multi(x)
@@ -62,7 +68,117 @@ impl EdgeExtensions for Edge {
             | Edge::FoldingRange(x)
             | Edge::DocumentLink(x)
             | Edge::DocumentSymbol(x)
-            | Edge::Diagnostic(x) => EitherEdgeData::Single(x),
+            | Edge::Diagnostic(x)
+            | Edge::Attach(x) => EitherEdgeData::Single(x),
+        }
+    }
+
+    fn label(&self) -> &'static str {
+        match self {
+            Edge::Contains(_) => "contains",
+            Edge::Moniker(_) => "moniker",
+            Edge::NextMoniker(_) => "nextMoniker",
+            Edge::Next(_) => "next",
+            Edge::PackageInformation(_) => "packageInformation",
+            Edge::Item(_) => "item",
+            Edge::Definition(_) => "textDocument/definition",
+            Edge::Declaration(_) => "textDocument/declaration",
+            Edge::Hover(_) => "textDocument/hover",
+            Edge::References(_) => "textDocument/references",
+            Edge::Implementation(_) => "textDocument/implementation",
+            Edge::TypeDefinition(_) => "textDocument/typeDefinition",
+            Edge::FoldingRange(_) => "textDocument/foldingRange",
+            Edge::DocumentLink(_) => "textDocument/documentLink",
+            Edge::DocumentSymbol(_) => "textDocument/documentSymbol",
+            Edge::Diagnostic(_) => "textDocument/diagnostic",
+            Edge::Attach(_) => "attach",
+        }
+    }
+}
+
+pub trait VertexExtensions {
+    /// Label of the vertex as written in the dump.
+    fn label(&self) -> &'static str;
+}
+
+impl VertexExtensions for Vertex {
+    fn label(&self) -> &'static str {
+        match self {
+            Vertex::MetaData(_) => "metaData",
+            Vertex::Source(_) => "source",
+            Vertex::Capabilities(_) => "capabilities",
+            Vertex::Project(_) => "project",
+            Vertex::Document(_) => "document",
+            Vertex::Range { .. } => "range",
+            Vertex::ResultSet(_) => "resultSet",
+            Vertex::Moniker(_) => "moniker",
+            Vertex::PackageInformation(_) => "packageInformation",
+            Vertex::Event(_) => "$event",
+            Vertex::DefinitionResult => "definitionResult",
+            Vertex::DeclarationResult => "declarationResult",
+            Vertex::TypeDefinitionResult => "typeDefinitionResult",
+            Vertex::ReferenceResult => "referenceResult",
+            Vertex::ImplementationResult => "implementationResult",
+            Vertex::FoldingRangeResult { .. } => "foldingRangeResult",
+            Vertex::HoverResult { .. } => "hoverResult",
+            Vertex::DocumentSymbolResult { .. } => "documentSymbolResult",
+            Vertex::DocumentLinkResult { .. } => "documentLinkResult",
+            Vertex::DiagnosticResult { .. } => "diagnosticResult",
+        }
+    }
+}
+
+pub trait EntryExtensions {
+    /// Calls `f` on every id stored in the entry, including references to other entries.
+    fn ids_mut(&mut self, f: &mut impl FnMut(&mut Id));
+}
+
+impl EntryExtensions for Entry {
+    fn ids_mut(&mut self, f: &mut impl FnMut(&mut Id)) {
+        f(&mut self.id);
+        match &mut self.data {
+            Element::Vertex(Vertex::Event(event)) => f(&mut event.data),
+            Element::Vertex(Vertex::DocumentSymbolResult {
+                result: DocumentSymbolOrRangeBasedVec::RangeBased(symbols),
+            }) => {
+                fn visit(symbols: &mut [RangeBasedDocumentSymbol], f: &mut impl FnMut(&mut Id)) {
+                    for symbol in symbols {
+                        f(&mut symbol.id);
+                        visit(&mut symbol.children, f);
+                    }
+                }
+                visit(symbols, f)
+            }
+            Element::Vertex(_) => {}
+            Element::Edge(edge) => {
+                if let Edge::Item(item) = edge {
+                    f(&mut item.document);
+                }
+                match edge {
+                    Edge::Contains(x) | Edge::Item(lsp_types::lsif::Item { edge_data: x, .. }) => {
+                        f(&mut x.out_v);
+                        x.in_vs.iter_mut().for_each(&mut *f);
+                    }
+                    Edge::Moniker(x)
+                    | Edge::NextMoniker(x)
+                    | Edge::Next(x)
+                    | Edge::PackageInformation(x)
+                    | Edge::Definition(x)
+                    | Edge::Declaration(x)
+                    | Edge::Hover(x)
+                    | Edge::References(x)
+                    | Edge::Implementation(x)
+                    | Edge::TypeDefinition(x)
+                    | Edge::FoldingRange(x)
+                    | Edge::DocumentLink(x)
+                    | Edge::DocumentSymbol(x)
+                    | Edge::Diagnostic(x)
+                    | Edge::Attach(x) => {
+                        f(&mut x.in_v);
+                        f(&mut x.out_v);
+                    }
+                }
+            }
         }
     }
 }
@@ -1,70 +1,185 @@
 use crate::lsif::converter::Converter;
 use crate::lsif::graph::Graph;
+use bson::DateTime;
 use bumpalo::Bump;
 use either::Either;
-use eyre::{eyre, OptionExt};
-use gix::ObjectId;
-use shatterbird_storage::model::Commit;
+use eyre::eyre;
+use lsif_ext::EntryExtensions;
+use lsp_types::lsif::{self, Element, Entry, Vertex};
+use shatterbird_storage::model::{Upload, UploadState};
+use shatterbird_storage::util::{monikers, uploads};
 use shatterbird_storage::{Id, Storage};
-use std::str::FromStr;
-use tracing::{info, instrument};
+use std::collections::BTreeSet;
+use std::iter;
+use std::path::PathBuf;
+use tracing::{info, instrument, warn};
 
 mod converter;
+pub mod export;
 mod graph;
+mod input;
 mod lsif_ext;
+mod report;
+mod roots;
+pub mod validate;
 
-#[derive(Debug, Clone)]
-pub struct RootMapping {
-    pub dir: String,
-    pub node: Either<Id<Commit>, ObjectId>,
+#[cfg(test)]
+mod tests;
+
+pub use report::ImportReport;
+pub use roots::Roots;
+
+#[instrument(skip_all)]
+pub async fn load_lsif(
+    storage: &Storage,
+    inputs: &[PathBuf],
+    roots: &Roots,
+    save: bool,
+) -> eyre::Result<ImportReport> {
+    load_entries(storage, read_inputs(inputs), roots, save).await
+}
+
+/// Reads one or more LSIF dumps as a single stream of entries.
+///
+/// Ids are local to a dump, so when several inputs are given they are prefixed with input index.
+fn read_inputs(inputs: &[PathBuf]) -> impl Iterator<Item = eyre::Result<Entry>> + '_ {
+    let namespaced = inputs.len() > 1;
+    let mut encoding = None;
+    inputs
+        .iter()
+        .enumerate()
+        .flat_map(move |(idx, path)| match input::read(path) {
+            Ok(entries) => Either::Left(entries.map(move |entry| {
+                let mut entry = entry?;
+                if namespaced {
+                    entry.ids_mut(&mut |id| {
+                        *id = match id {
+                            lsif::Id::Number(n) => lsif::Id::String(format!("{idx}:{n}")),
+                            lsif::Id::String(s) => lsif::Id::String(format!("{idx}:{s}")),
+                        }
+                    });
+                }
+                Ok(entry)
+            })),
+            Err(e) => Either::Right(iter::once(Err(e))),
+        })
+        .map(move |entry| {
+            if let Ok(Entry {
+                data: Element::Vertex(Vertex::MetaData(meta)),
+                ..
+            }) = &entry
+            {
+                match encoding {
+                    None => encoding = Some(meta.position_encoding),
+                    Some(enc) if enc != meta.position_encoding => {
+                        return Err(eyre!(
+                            "inputs use different position encodings: {:?} and {:?}",
+                            enc,
+                            meta.position_encoding
+                        ))
+                    }
+                    Some(_) => {}
+                }
+            }
+            entry
+        })
 }
 
 #[instrument(skip_all)]
-pub async fn load_lsif<R: std::io::BufRead>(
+pub async fn load_entries(
     storage: &Storage,
-    input: R,
-    roots: Vec<RootMapping>,
+    entries: impl IntoIterator<Item = eyre::Result<Entry>>,
+    roots: &Roots,
     save: bool,
-) -> eyre::Result<()> {
+) -> eyre::Result<ImportReport> {
     info!("parsing graph");
     let arena = Bump::new();
     let mut graph = Graph::new(&arena);
-    for line in input.lines() {
-        let line = line?;
-        let entry = serde_json::from_str(&line)
-            .map_err(|e| eyre!("failed to parse line {}: {}", line, e))?;
-        graph.add(entry)
+    for entry in entries {
+        graph.add(entry?)
     }
 
+    let upload_roots = roots.resolve(storage, graph.project_root()).await?;
+    let schemes = roots.accepted_schemes(&upload_roots);
+    let mut commits = Vec::new();
+    for root in &upload_roots {
+        if !commits.contains(&root.commit) {
+            commits.push(root.commit);
+        }
+    }
+    let upload = Upload {
+        id: Id::new(),
+        commits,
+        project: project_key(&graph),
+        languages: languages(&graph),
+        tool_info: graph.metadata().and_then(|x| x.tool_info.clone()),
+        created_at: DateTime::now(),
+        state: UploadState::Processing,
+        roots: upload_roots,
+    };
+
     info!("converting graph");
-    let converter = Converter::new(storage, &graph, roots);
+    let converter = Converter::new(storage, &graph, &upload.roots, schemes, upload.id);
     converter.load().await?;
+    let mut report = converter.report();
 
     if save {
-        info!("saving");
-        converter.save().await?;
+        info!("saving upload {} for project {}", upload.id, upload.project);
+        storage.insert_one(&upload).await?;
+        let saved = async {
+            converter.save().await?;
+            let monikers = monikers::index(storage, upload.id).await?;
+            info!("indexed {} import and export monikers", monikers);
+            uploads::activate(storage, &upload).await
+        };
+        let superseded = match saved.await {
+            Ok(x) => x,
+            Err(e) => {
+                warn!("failed to save upload {}, deleting it", upload.id);
+                uploads::delete(storage, upload.id).await?;
+                return Err(e);
+            }
+        };
+        for previous in superseded {
+            info!("deleting superseded upload {}", previous);
+            uploads::delete(storage, previous).await?;
+        }
+        report.upload = Some(upload.id);
     }
 
-    Ok(())
+    Ok(report)
 }
 
-impl FromStr for RootMapping {
-    type Err = eyre::Report;
-
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let (dir, node) = s.rsplit_once('=').ok_or_eyre("invalid root mapping")?;
-        let dir = dir.to_string();
-        let node = match node.len() {
-            24 => Either::Left(
-                node.parse()
-                    .map_err(|e| eyre!("failed to parse node id: {e}"))?,
-            ),
-            40 => Either::Right(
-                node.parse()
-                    .map_err(|e| eyre!("failed to parse node id: {e}"))?,
-            ),
-            _ => return Err(eyre!("invalid root id: {node}")),
-        };
-        Ok(RootMapping { dir, node })
+/// Identifies the project of a dump by names of its `project` vertices,
+/// so that projects of the same language in one repository don't supersede each other.
+///
+/// Projects without a name are identified by their resource, and then by their language.
+fn project_key(graph: &Graph) -> String {
+    let keys = graph
+        .projects()
+        .iter()
+        .map(|x| {
+            let kind = x.kind.to_lowercase();
+            match (&x.name, &x.resource) {
+                (Some(name), _) => format!("{kind}:{name}"),
+                (None, Some(resource)) => format!("{kind}:{resource}"),
+                (None, None) => kind,
+            }
+        })
+        .collect::<BTreeSet<_>>();
+    if keys.is_empty() {
+        warn!("no project vertex found");
+        return "unknown".to_string();
     }
+    keys.into_iter().collect::<Vec<_>>().join("+")
+}
+
+/// Languages of `project` vertices of a dump
+fn languages(graph: &Graph) -> Vec<String> {
+    let kinds = graph
+        .projects()
+        .iter()
+        .map(|x| x.kind.to_lowercase())
+        .collect::<BTreeSet<_>>();
+    kinds.into_iter().collect()
 }
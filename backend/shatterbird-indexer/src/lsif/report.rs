@@ -0,0 +1,69 @@
+use std::collections::BTreeMap;
+use std::fmt;
+
+use serde::Serialize;
+use shatterbird_storage::model::Upload;
+use shatterbird_storage::Id;
+
+/// Summary of a single LSIF import, printed after every load.
+#[derive(Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportReport {
+    /// Upload the data was saved as, `None` for dry runs.
+    pub upload: Option<Id<Upload>>,
+    pub documents_resolved: usize,
+    /// Documents which are not under any of the roots or have an unsupported URI scheme.
+    pub documents_skipped: usize,
+    pub vertices: BTreeMap<&'static str, usize>,
+    pub edges: BTreeMap<&'static str, usize>,
+    /// `$event` vertices, which are not stored.
+    pub events_dropped: usize,
+    /// Ranges whose lines are not present in the stored file.
+    pub ranges_unmapped: usize,
+    /// Warnings grouped by document URI.
+    pub warnings: BTreeMap<String, Vec<String>>,
+}
+
+impl ImportReport {
+    pub fn warn(&mut self, uri: &str, message: String) {
+        self.warnings
+            .entry(uri.to_string())
+            .or_default()
+            .push(message);
+    }
+}
+
+impl fmt::Display for ImportReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.upload {
+            Some(id) => writeln!(f, "saved as upload {}", id)?,
+            None => writeln!(f, "dry run, nothing saved")?,
+        }
+        writeln!(
+            f,
+            "documents: {} resolved, {} skipped",
+            self.documents_resolved, self.documents_skipped
+        )?;
+        writeln!(
+            f,
+            "vertices: {} ({} events dropped)",
+            self.vertices.values().sum::<usize>(),
+            self.events_dropped
+        )?;
+        for (kind, count) in &self.vertices {
+            writeln!(f, "  {}: {}", kind, count)?;
+        }
+        writeln!(f, "edges: {}", self.edges.values().sum::<usize>())?;
+        for (kind, count) in &self.edges {
+            writeln!(f, "  {}: {}", kind, count)?;
+        }
+        write!(f, "ranges not mapped to lines: {}", self.ranges_unmapped)?;
+        for (uri, warnings) in &self.warnings {
+            write!(f, "\n{}:", uri)?;
+            for warning in warnings {
+                write!(f, "\n  {}", warning)?;
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,275 @@
+use std::path::{Component, Path, PathBuf};
+use std::str::FromStr;
+
+use either::Either;
+use eyre::{eyre, OptionExt, WrapErr};
+use gix::object::Kind;
+use gix::ObjectId;
+use lsp_types::Url;
+use shatterbird_storage::model::{Commit, UploadRoot};
+use shatterbird_storage::{Id, Storage};
+use tracing::{info, instrument};
+
+/// Maps documents under a prefix to a stored commit.
+///
+/// The prefix is either a path of `file` URIs or a full URI like `git://host/repo/`.
+#[derive(Debug, Clone)]
+pub struct RootMapping {
+    pub dir: String,
+    pub node: Either<Id<Commit>, ObjectId>,
+}
+
+/// How documents of a dump are mapped to stored commits.
+#[derive(clap::Args, Debug, Clone)]
+pub struct Roots {
+    /// Explicit `<prefix>=<commit>` mappings, where prefix is a path or a URI like
+    /// `git://host/repo/`, and commit is either `id:<database id>` or `oid:<git hash>`.
+    /// When omitted, the mapping is built from the workspace root of the dump.
+    #[arg(long)]
+    pub roots: Vec<RootMapping>,
+
+    /// Git repository containing the indexed commit,
+    /// discovered from the workspace root of the dump by default.
+    #[arg(long, conflicts_with = "roots")]
+    pub repo: Option<PathBuf>,
+
+    /// Revision of the repository that was indexed.
+    #[arg(long, default_value = "HEAD", conflicts_with = "roots")]
+    pub rev: String,
+
+    /// Store the commit first if it is not in the database yet.
+    #[arg(long, conflicts_with = "roots")]
+    pub index_commit: bool,
+
+    /// URI schemes of documents to load, other documents are skipped with a warning.
+    /// Schemes used in `--roots` prefixes are accepted as well.
+    #[arg(long = "scheme", default_value = "file")]
+    pub schemes: Vec<String>,
+}
+
+impl Roots {
+    /// Schemes of documents which can be loaded with the given roots.
+    pub fn accepted_schemes(&self, roots: &[UploadRoot]) -> Vec<String> {
+        let mut result = self
+            .schemes
+            .iter()
+            .map(|x| x.to_ascii_lowercase())
+            .collect::<Vec<_>>();
+        for root in roots {
+            let key = prefix_key(&root.prefix);
+            let (scheme, _) = key.split_once("://").expect("keys always contain a scheme");
+            if !result.iter().any(|x| x == scheme) {
+                result.push(scheme.to_string());
+            }
+        }
+        result
+    }
+
+    #[instrument(skip_all, err)]
+    pub async fn resolve(
+        &self,
+        storage: &Storage,
+        project_root: Option<&Url>,
+    ) -> eyre::Result<Vec<UploadRoot>> {
+        if !self.roots.is_empty() {
+            return resolve_explicit(storage, &self.roots).await;
+        }
+
+        let project_root = project_root.ok_or_eyre(
+            "dump has no workspace root in source or metaData, pass --roots explicitly",
+        )?;
+        let project_path = project_root.to_file_path().map_err(|()| {
+            eyre!("workspace root {project_root} is not a local path, pass --roots explicitly")
+        })?;
+
+        let repo = match &self.repo {
+            Some(path) => gix::open(path)
+                .wrap_err_with(|| format!("failed to open repository {}", path.display()))?,
+            None => gix::discover(&project_path).wrap_err_with(|| {
+                format!(
+                    "no repository found at workspace root {}, pass --repo or --roots",
+                    project_path.display()
+                )
+            })?,
+        };
+        let work_dir = repo
+            .work_dir()
+            .ok_or_eyre("repository has no working directory")?
+            .canonicalize()?;
+
+        let commit = repo
+            .rev_parse_single(self.rev.as_str())
+            .wrap_err_with(|| format!("failed to resolve {}", self.rev))?
+            .object()?
+            .peel_to_kind(Kind::Commit)?
+            .try_into_commit()?;
+
+        // Documents are mapped relative to the repository root, so the workspace root loses
+        // its path inside the repository. A dump from another machine has a workspace root
+        // which does not exist here, its path inside the repository is guessed from the commit
+        let depth = match project_path.canonicalize() {
+            Ok(path) if path.starts_with(&work_dir) => {
+                path.strip_prefix(&work_dir)?.components().count()
+            }
+            _ => {
+                let tree = commit.tree()?;
+                let mut buf = Vec::new();
+                remote_depth(&project_path, |path| {
+                    matches!(
+                        tree.clone().lookup_entry(path.iter().copied(), &mut buf),
+                        Ok(Some(entry)) if entry.mode().is_tree()
+                    )
+                })
+            }
+        };
+        let prefix = strip_segments(project_root, depth);
+
+        let id = match storage.get_by_oid::<Commit>(commit.id).await? {
+            Some(x) => x.id,
+            None if self.index_commit => {
+                info!("storing commit {}", commit.id);
+                crate::git::index_commit(storage, &repo, commit, 0).await?
+            }
+            None => {
+                return Err(eyre!(
+                    "commit {} ({}) is not stored yet, run the git command or pass --index-commit",
+                    commit.id,
+                    self.rev
+                ))
+            }
+        };
+        info!("mapping {} to {}", prefix.path(), id);
+        Ok(vec![UploadRoot {
+            prefix: prefix.path().to_string(),
+            commit: id,
+        }])
+    }
+}
+
+/// Key of a document URI in the roots trie, `scheme://authority/path`.
+pub fn uri_key(uri: &Url) -> String {
+    format!("{}://{}{}", uri.scheme(), uri.authority(), uri.path())
+}
+
+/// Key of a root prefix in the roots trie, bare paths are treated as `file` URIs.
+pub fn prefix_key(prefix: &str) -> String {
+    match Url::parse(prefix) {
+        // Single letter schemes are Windows drives
+        Ok(url) if url.scheme().len() > 1 => uri_key(&url),
+        _ => format!("file://{}", prefix),
+    }
+}
+
+/// Number of trailing components of a foreign workspace root which form its path inside
+/// the repository, the longest tail that is a directory of the indexed commit wins.
+fn remote_depth(project_path: &Path, mut is_dir: impl FnMut(&[&str]) -> bool) -> usize {
+    let components = project_path
+        .components()
+        .filter_map(|x| match x {
+            Component::Normal(x) => x.to_str(),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    (1..components.len())
+        .map(|skip| &components[skip..])
+        .find(|tail| is_dir(tail))
+        .map_or(0, |tail| tail.len())
+}
+
+/// Removes `depth` trailing segments from a directory URI, which keeps its trailing slash.
+fn strip_segments(uri: &Url, depth: usize) -> Url {
+    let segments = uri
+        .path()
+        .split('/')
+        .filter(|x| !x.is_empty())
+        .collect::<Vec<_>>();
+    let kept = &segments[..segments.len().saturating_sub(depth)];
+    let mut result = uri.clone();
+    result.set_path(&format!(
+        "/{}",
+        kept.iter().map(|x| format!("{x}/")).collect::<String>()
+    ));
+    result
+}
+
+async fn resolve_explicit(
+    storage: &Storage,
+    roots: &[RootMapping],
+) -> eyre::Result<Vec<UploadRoot>> {
+    let mut result = Vec::new();
+    for root in roots {
+        let commit: Option<Commit> = match root.node {
+            Either::Left(id) => storage.get(id).await?,
+            Either::Right(oid) => storage.get_by_oid(oid).await?,
+        };
+        let commit = commit.ok_or_eyre(eyre!("commit {} not found in DB", root.node))?;
+        result.push(UploadRoot {
+            prefix: root.dir.clone(),
+            commit: commit.id,
+        });
+    }
+    Ok(result)
+}
+
+impl FromStr for RootMapping {
+    type Err = eyre::Report;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        // Commit ids never contain `=`, while paths may
+        let (dir, node) = s
+            .rsplit_once('=')
+            .ok_or_else(|| eyre!("invalid root mapping {s}, expected <path prefix>=<commit>"))?;
+        let dir = dir.to_string();
+        let node = match node.split_once(':') {
+            Some(("id", id)) => Either::Left(
+                id.parse()
+                    .map_err(|e| eyre!("failed to parse database id {id}: {e}"))?,
+            ),
+            Some(("oid", oid)) => Either::Right(
+                ObjectId::from_hex(oid.as_bytes())
+                    .map_err(|e| eyre!("failed to parse git hash {oid}: {e}"))?,
+            ),
+            // Kept for compatibility with mappings written before prefixes were introduced
+            None if node.len() == 24 => Either::Left(
+                node.parse()
+                    .map_err(|e| eyre!("failed to parse database id {node}: {e}"))?,
+            ),
+            None if node.len() == 40 => Either::Right(
+                ObjectId::from_hex(node.as_bytes())
+                    .map_err(|e| eyre!("failed to parse git hash {node}: {e}"))?,
+            ),
+            _ => {
+                return Err(eyre!(
+                    "invalid commit {node}, expected id:<database id> or oid:<git hash>"
+                ))
+            }
+        };
+        Ok(RootMapping { dir, node })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn subdirectory_maps_to_repository_root() {
+        let root = Url::parse("file:///home/user/repo/packages/web").unwrap();
+        assert_eq!(strip_segments(&root, 2).as_str(), "file:///home/user/repo/");
+        assert_eq!(
+            strip_segments(&root, 0).as_str(),
+            "file:///home/user/repo/packages/web/"
+        );
+        let root = Url::parse("file:///repo/").unwrap();
+        assert_eq!(strip_segments(&root, 5).as_str(), "file:///");
+    }
+
+    #[test]
+    fn remote_subdirectory_is_found_in_commit() {
+        let dirs = [vec!["packages"], vec!["packages", "web"], vec!["web"]];
+        let is_dir = |path: &[&str]| dirs.iter().any(|x| x == path);
+        let path = Path::new("/ci/build/repo/packages/web");
+        assert_eq!(remote_depth(path, is_dir), 2);
+        assert_eq!(remote_depth(Path::new("/ci/build/repo"), is_dir), 0);
+    }
+}
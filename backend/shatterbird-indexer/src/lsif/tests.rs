@@ -0,0 +1,327 @@
+//! Round trip of LSIF dumps through the stored model: a dump is imported, exported,
+//! and the export is imported and exported again.
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+
+use bumpalo::Bump;
+use gix::ObjectId;
+use lsp_types::lsif::{self, Element, Entry};
+use lsp_types::Url;
+use radix_trie::Trie;
+use serde_json::Value;
+use shatterbird_storage::model::{Commit, FileContent, Line, Node, UploadRoot};
+use shatterbird_storage::{Id, Storage};
+
+use super::converter::{Converter, FileWithPath};
+use super::export;
+use super::graph::Graph;
+use super::roots::prefix_key;
+
+/// Commit the dump is imported into, with enough empty lines in every document
+struct Files {
+    root: Url,
+    oid: ObjectId,
+    commit: Id<Commit>,
+    nodes: HashMap<Url, Node>,
+}
+
+impl Files {
+    fn new(dump: &[Entry]) -> Self {
+        let mut root = None;
+        let mut lines = 0;
+        let mut uris = Vec::new();
+        for entry in dump {
+            match &entry.data {
+                Element::Vertex(lsif::Vertex::MetaData(x)) => {
+                    root = root.or_else(|| x.project_root.clone())
+                }
+                Element::Vertex(lsif::Vertex::Source(x)) => {
+                    root = x.workspace_root.clone().or(root)
+                }
+                Element::Vertex(lsif::Vertex::Document(x)) => uris.push(x.uri.clone()),
+                Element::Vertex(lsif::Vertex::Range { range, .. }) => {
+                    lines = lines.max(range.end.line as usize + 1)
+                }
+                _ => {}
+            }
+        }
+        let root = root.expect("dump has a project root");
+        let nodes = uris
+            .into_iter()
+            .map(|uri| {
+                let node = Node {
+                    id: Id::new(),
+                    oid: ObjectId::null(gix::hash::Kind::Sha1),
+                    content: FileContent::Text {
+                        size: 0,
+                        lines: (0..lines).map(|_| Id::new()).collect(),
+                    },
+                };
+                (uri, node)
+            })
+            .collect();
+        Files {
+            root: format!("{}/", root.as_str().trim_end_matches('/'))
+                .parse()
+                .unwrap(),
+            oid: ObjectId::null(gix::hash::Kind::Sha1),
+            commit: Id::new(),
+            nodes,
+        }
+    }
+
+    fn resolve(&self, id: &lsif::Id, document: &lsif::Document) -> FileWithPath {
+        let node = self.nodes[&document.uri].clone();
+        let relative = &document.uri.as_str()[self.root.as_str().len()..];
+        FileWithPath {
+            id: id.clone(),
+            document: lsif::Document {
+                contents: None,
+                ..document.clone()
+            },
+            path: vec![node.id],
+            node,
+            texts: HashMap::new(),
+            uri: document.uri.clone(),
+            location: format!("bird:///{}/{}", self.oid, relative)
+                .parse()
+                .unwrap(),
+            commit: self.commit,
+        }
+    }
+
+    fn lines(&self) -> HashMap<Id<Node>, Vec<Id<Line>>> {
+        self.nodes
+            .values()
+            .filter_map(|x| match &x.content {
+                FileContent::Text { lines, .. } => Some((x.id, lines.clone())),
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+/// Imports a dump the way `lsif --save` does, but without resolving documents in the database,
+/// and exports the result with the original document URIs
+fn roundtrip(storage: &Storage, files: &Files, dump: &[Entry]) -> Vec<Entry> {
+    let arena = Bump::new();
+    let mut graph = Graph::new(&arena);
+    for entry in dump {
+        graph.add(entry.clone());
+    }
+    let roots = [UploadRoot {
+        prefix: files.root.to_string(),
+        commit: files.commit,
+    }];
+    let converter = Converter::new(storage, &graph, &roots, vec!["file".into()], Id::new());
+    let documents = dump
+        .iter()
+        .filter_map(|x| match &x.data {
+            Element::Vertex(lsif::Vertex::Document(doc)) => Some(files.resolve(&x.id, doc)),
+            _ => None,
+        })
+        .collect();
+    converter.load_files(documents).unwrap();
+    let (ranges, vertices, edges) = converter.contents();
+
+    let positions = export::positions(&ranges, &files.lines(), &HashMap::new());
+    let mut roots = Trie::new();
+    roots.insert(prefix_key(files.root.as_str()), files.oid);
+    let mut out = Vec::new();
+    export::write_lsif(
+        &mut out,
+        roots,
+        Some(files.root.clone()),
+        positions,
+        vertices,
+        edges,
+    )
+    .unwrap();
+    parse(std::str::from_utf8(&out).unwrap())
+}
+
+fn parse(jsonl: &str) -> Vec<Entry> {
+    jsonl
+        .lines()
+        .map(|x| serde_json::from_str(x).unwrap())
+        .collect()
+}
+
+fn hash(value: impl Hash) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    value.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Edge of a dump as `(label and property, out, ins, document)`
+type Link = (String, String, Vec<String>, Option<String>);
+
+/// Hashes of vertices and edges which don't depend on ids and order of entries.
+///
+/// Vertices are named by their contents and then refined by names of their neighbours,
+/// so that equal result sets with different results get different names.
+fn shape(dump: &[Entry]) -> (Vec<u64>, Vec<u64>) {
+    let key = |x: &Value| x.to_string();
+    let mut names = HashMap::new();
+    let mut links = Vec::<Link>::new();
+    for entry in dump {
+        let mut value = serde_json::to_value(entry).unwrap();
+        let id = key(&value["id"]);
+        match &entry.data {
+            Element::Vertex(_) => {
+                value.as_object_mut().unwrap().remove("id");
+                // Range-based document symbols refer to ranges by ids
+                let mut refs = Vec::new();
+                take_ids(&mut value, &mut refs);
+                if !refs.is_empty() {
+                    links.push(("symbols".into(), id.clone(), refs, None));
+                }
+                names.insert(id, hash(value.to_string()));
+            }
+            Element::Edge(_) => {
+                let ins = match (&value["inV"], &value["inVs"]) {
+                    (Value::Null, Value::Array(x)) => x.iter().map(key).collect(),
+                    (x, _) => vec![key(x)],
+                };
+                let label = format!("{} {}", value["label"], value["property"]);
+                let document = value.get("document").map(key);
+                links.push((label, key(&value["outV"]), ins, document));
+            }
+        }
+    }
+
+    for _ in 0..4 {
+        let mut neighbours = HashMap::<&String, Vec<u64>>::new();
+        for (label, out, ins, document) in &links {
+            let mut ins_names = ins.iter().map(|x| names[x]).collect::<Vec<_>>();
+            ins_names.sort();
+            let document = document.as_ref().map(|x| names[x]);
+            neighbours
+                .entry(out)
+                .or_default()
+                .push(hash((label, "out", &ins_names, document)));
+            for x in ins {
+                neighbours
+                    .entry(x)
+                    .or_default()
+                    .push(hash((label, "in", names[out], document)));
+            }
+        }
+        names = names
+            .iter()
+            .map(|(id, name)| {
+                let mut around = neighbours.remove(id).unwrap_or_default();
+                around.sort();
+                (id.clone(), hash((name, around)))
+            })
+            .collect();
+    }
+
+    let mut vertices = names.values().copied().collect::<Vec<_>>();
+    vertices.sort();
+    let mut edges = links
+        .iter()
+        .map(|(label, out, ins, document)| {
+            let mut ins = ins.iter().map(|x| names[x]).collect::<Vec<_>>();
+            ins.sort();
+            hash((label, names[out], ins, document.as_ref().map(|x| names[x])))
+        })
+        .collect::<Vec<_>>();
+    edges.sort();
+    (vertices, edges)
+}
+
+/// Replaces ids nested in a vertex with nulls, collecting them
+fn take_ids(value: &mut Value, ids: &mut Vec<String>) {
+    match value {
+        Value::Object(map) => {
+            for (k, v) in map.iter_mut() {
+                match k.as_str() {
+                    "id" => ids.push(std::mem::take(v).to_string()),
+                    _ => take_ids(v, ids),
+                }
+            }
+        }
+        Value::Array(items) => items.iter_mut().for_each(|x| take_ids(x, ids)),
+        _ => {}
+    }
+}
+
+/// Ranges of the dump with URIs of documents containing them
+fn ranges(dump: &[Entry]) -> Vec<(String, lsp_types::Range)> {
+    let mut vertices = HashMap::new();
+    for entry in dump {
+        if let Element::Vertex(v) = &entry.data {
+            vertices.insert(serde_json::to_string(&entry.id).unwrap(), v);
+        }
+    }
+    let mut result = Vec::new();
+    for entry in dump {
+        let Element::Edge(lsif::Edge::Contains(data)) = &entry.data else {
+            continue;
+        };
+        let Some(lsif::Vertex::Document(doc)) =
+            vertices.get(&serde_json::to_string(&data.out_v).unwrap())
+        else {
+            continue;
+        };
+        for id in &data.in_vs {
+            if let Some(lsif::Vertex::Range { range, .. }) =
+                vertices.get(&serde_json::to_string(id).unwrap())
+            {
+                result.push((doc.uri.to_string(), *range));
+            }
+        }
+    }
+    result.sort_by_key(|(uri, x)| (uri.clone(), x.start, x.end));
+    result
+}
+
+fn hovers(dump: &[Entry]) -> Vec<String> {
+    let mut result = dump
+        .iter()
+        .filter_map(|x| match &x.data {
+            Element::Vertex(lsif::Vertex::HoverResult { result }) => {
+                Some(serde_json::to_string(result).unwrap())
+            }
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    result.sort();
+    result
+}
+
+async fn check(fixture: &str) {
+    // Documents are resolved by the test, so the storage is never queried
+    let storage = Storage::connect("mongodb://127.0.0.1:1/roundtrip")
+        .await
+        .unwrap();
+    let original = parse(fixture);
+    let files = Files::new(&original);
+
+    let exported = roundtrip(&storage, &files, &original);
+    assert_eq!(ranges(&exported), ranges(&original));
+    assert_eq!(hovers(&exported), hovers(&original));
+
+    let again = roundtrip(&storage, &files, &exported);
+    assert_eq!(again.len(), exported.len());
+    assert_eq!(shape(&again), shape(&exported));
+}
+
+#[tokio::test]
+async fn tsc_dump() {
+    check(include_str!(
+        "../../../thirdparty/lsp-types/tests/tsc-unix.lsif"
+    ))
+    .await;
+}
+
+#[tokio::test]
+async fn spec_0_6_dump() {
+    check(include_str!(
+        "../../../thirdparty/lsp-types/tests/spec-0.6.lsif"
+    ))
+    .await;
+}
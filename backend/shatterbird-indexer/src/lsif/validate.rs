@@ -0,0 +1,316 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use eyre::OptionExt;
+use lsp_types::lsif::{Edge, Element, Encoding, Id, ItemKind, Vertex};
+use serde::Serialize;
+use tracing::{info, instrument, warn};
+
+use super::lsif_ext::{EdgeExtensions, VertexExtensions};
+
+/// Maps URI prefix of the dump to a local checkout, used to check range bounds.
+#[derive(Debug, Clone)]
+pub struct LocalRoot {
+    pub prefix: String,
+    pub dir: PathBuf,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Report {
+    pub entries: usize,
+    pub problems: Vec<Problem>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Problem {
+    pub kind: ProblemKind,
+    /// Entry with the problem, `None` for entries which could not be parsed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<Id>,
+    pub message: String,
+}
+
+#[derive(Debug, Copy, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ProblemKind {
+    MalformedEntry,
+    DuplicateId,
+    DanglingVertex,
+    WrongEdgeKind,
+    InvalidItemDocument,
+    RangeOutOfBounds,
+}
+
+/// Checks LSIF dumps against the specification without loading them into the database.
+#[instrument(skip_all)]
+pub fn validate(inputs: &[PathBuf], roots: &[LocalRoot]) -> eyre::Result<Report> {
+    let mut entries = Vec::new();
+    let mut problems = Vec::new();
+    for entry in super::read_inputs(inputs) {
+        match entry {
+            Ok(entry) => entries.push(entry),
+            Err(e) => problems.push(Problem {
+                kind: ProblemKind::MalformedEntry,
+                id: None,
+                message: format!("{:#}", e),
+            }),
+        }
+    }
+    info!("read {} entries", entries.len());
+
+    let mut vertices = HashMap::new();
+    let mut encoding = Encoding::Utf16;
+    for entry in &entries {
+        if let Element::Vertex(v) = &entry.data {
+            if let Vertex::MetaData(meta) = v {
+                encoding = meta.position_encoding;
+            }
+            if vertices.insert(&entry.id, v).is_some() {
+                problems.push(Problem {
+                    kind: ProblemKind::DuplicateId,
+                    id: Some(entry.id.clone()),
+                    message: "vertex id is used more than once".to_string(),
+                });
+            }
+        }
+    }
+
+    let mut edge_ids = HashSet::new();
+    let mut contained = Vec::new();
+    for entry in &entries {
+        let Element::Edge(edge) = &entry.data else {
+            continue;
+        };
+        if vertices.contains_key(&entry.id) || !edge_ids.insert(&entry.id) {
+            problems.push(Problem {
+                kind: ProblemKind::DuplicateId,
+                id: Some(entry.id.clone()),
+                message: "edge id is used more than once".to_string(),
+            });
+        }
+
+        let (out_kinds, in_kinds) = allowed_labels(edge);
+        for data in edge.edge_data().each() {
+            for (role, v, allowed) in [
+                ("outV", data.out_v, out_kinds),
+                ("inV", data.in_v, in_kinds),
+            ] {
+                match vertices.get(v) {
+                    None => problems.push(Problem {
+                        kind: ProblemKind::DanglingVertex,
+                        id: Some(entry.id.clone()),
+                        message: format!(
+                            "{} of {} edge refers to missing vertex {}",
+                            role,
+                            edge.label(),
+                            id_to_string(v)
+                        ),
+                    }),
+                    Some(vertex) if !allowed.contains(&vertex.label()) => problems.push(Problem {
+                        kind: ProblemKind::WrongEdgeKind,
+                        id: Some(entry.id.clone()),
+                        message: format!(
+                            "{} of {} edge is {} {}, expected {}",
+                            role,
+                            edge.label(),
+                            vertex.label(),
+                            id_to_string(v),
+                            allowed.join(" or ")
+                        ),
+                    }),
+                    Some(_) => {}
+                }
+            }
+        }
+
+        match edge {
+            Edge::Item(item) => match vertices.get(&item.document) {
+                Some(Vertex::Document(_)) => {}
+                found => problems.push(Problem {
+                    kind: ProblemKind::InvalidItemDocument,
+                    id: Some(entry.id.clone()),
+                    message: match found {
+                        None => format!("document {} does not exist", id_to_string(&item.document)),
+                        Some(v) => {
+                            format!("document {} is {}", id_to_string(&item.document), v.label())
+                        }
+                    },
+                }),
+            },
+            Edge::Contains(data) => {
+                if let Some(Vertex::Document(_)) = vertices.get(&data.out_v) {
+                    contained.push(data);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    if !roots.is_empty() {
+        for data in contained {
+            let Some(Vertex::Document(doc)) = vertices.get(&data.out_v) else {
+                continue;
+            };
+            let Some(lines) = read_document(roots, doc.uri.as_str())? else {
+                continue;
+            };
+            for range_id in &data.in_vs {
+                let Some(Vertex::Range { range, .. }) = vertices.get(range_id) else {
+                    continue;
+                };
+                for (name, pos) in [("start", range.start), ("end", range.end)] {
+                    let len = lines.get(pos.line as usize).map(|line| match encoding {
+                        Encoding::Utf8 => line.len(),
+                        Encoding::Utf16 => line.encode_utf16().count(),
+                    });
+                    let message = match len {
+                        None => format!(
+                            "{} line {} is past the end of {} ({} lines)",
+                            name,
+                            pos.line,
+                            doc.uri,
+                            lines.len()
+                        ),
+                        Some(len) if pos.character as usize > len => format!(
+                            "{} character {} is past the end of line {} in {} ({} characters)",
+                            name, pos.character, pos.line, doc.uri, len
+                        ),
+                        Some(_) => continue,
+                    };
+                    problems.push(Problem {
+                        kind: ProblemKind::RangeOutOfBounds,
+                        id: Some(range_id.clone()),
+                        message,
+                    });
+                }
+            }
+        }
+    }
+
+    Ok(Report {
+        entries: entries.len(),
+        problems,
+    })
+}
+
+fn id_to_string(id: &Id) -> String {
+    match id {
+        Id::Number(n) => n.to_string(),
+        Id::String(s) => s.clone(),
+    }
+}
+
+fn allowed_labels(edge: &Edge) -> (&'static [&'static str], &'static [&'static str]) {
+    const RANGE_OR_RESULT_SET: &[&str] = &["range", "resultSet"];
+    match edge {
+        Edge::Contains(_) => (&["project", "document"], &["document", "range"]),
+        Edge::Next(_) => (RANGE_OR_RESULT_SET, &["resultSet"]),
+        Edge::Moniker(_) => (RANGE_OR_RESULT_SET, &["moniker"]),
+        Edge::NextMoniker(_) => (&["moniker"], &["moniker"]),
+        Edge::PackageInformation(_) => (&["moniker"], &["packageInformation"]),
+        Edge::Item(item) => (
+            &[
+                "definitionResult",
+                "declarationResult",
+                "typeDefinitionResult",
+                "referenceResult",
+                "implementationResult",
+            ],
+            match item.property {
+                Some(ItemKind::ReferenceResults) => &["referenceResult"],
+                Some(ItemKind::ImplementationResults) => &["implementationResult"],
+                Some(ItemKind::ReferenceLinks | ItemKind::ImplementationLinks) => &["moniker"],
+                _ => &["range"],
+            },
+        ),
+        Edge::Definition(_) => (RANGE_OR_RESULT_SET, &["definitionResult"]),
+        Edge::Declaration(_) => (RANGE_OR_RESULT_SET, &["declarationResult"]),
+        Edge::Hover(_) => (RANGE_OR_RESULT_SET, &["hoverResult"]),
+        Edge::References(_) => (RANGE_OR_RESULT_SET, &["referenceResult"]),
+        Edge::Implementation(_) => (RANGE_OR_RESULT_SET, &["implementationResult"]),
+        Edge::TypeDefinition(_) => (RANGE_OR_RESULT_SET, &["typeDefinitionResult"]),
+        Edge::FoldingRange(_) => (&["document"], &["foldingRangeResult"]),
+        Edge::DocumentLink(_) => (&["document"], &["documentLinkResult"]),
+        Edge::DocumentSymbol(_) => (&["document"], &["documentSymbolResult"]),
+        Edge::Diagnostic(_) => (&["document", "project"], &["diagnosticResult"]),
+        Edge::Attach(_) => (&["moniker"], &["moniker"]),
+    }
+}
+
+/// Reads lines of a document from the local root it belongs to, if there is one.
+fn read_document(roots: &[LocalRoot], uri: &str) -> eyre::Result<Option<Vec<String>>> {
+    let Some((root, rest)) = roots
+        .iter()
+        .filter_map(|root| Some((root, uri.strip_prefix(&root.prefix)?)))
+        .max_by_key(|(root, _)| root.prefix.len())
+    else {
+        warn!("document {} is not under any of the roots", uri);
+        return Ok(None);
+    };
+    let path = root.dir.join(rest.trim_start_matches('/'));
+    let text = match std::fs::read_to_string(&path) {
+        Ok(text) => text,
+        Err(e) => {
+            warn!("failed to read {}: {}", path.display(), e);
+            return Ok(None);
+        }
+    };
+    Ok(Some(
+        text.split('\n')
+            .map(|line| line.strip_suffix('\r').unwrap_or(line).to_string())
+            .collect(),
+    ))
+}
+
+impl FromStr for LocalRoot {
+    type Err = eyre::Report;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (prefix, dir) = s.split_once('=').ok_or_eyre("invalid root mapping")?;
+        Ok(LocalRoot {
+            prefix: prefix.to_string(),
+            dir: dir.into(),
+        })
+    }
+}
+
+impl fmt::Display for ProblemKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            ProblemKind::MalformedEntry => "malformed entry",
+            ProblemKind::DuplicateId => "duplicate id",
+            ProblemKind::DanglingVertex => "dangling vertex",
+            ProblemKind::WrongEdgeKind => "wrong edge kind",
+            ProblemKind::InvalidItemDocument => "invalid item document",
+            ProblemKind::RangeOutOfBounds => "range out of bounds",
+        };
+        f.write_str(name)
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for problem in &self.problems {
+            match &problem.id {
+                Some(id) => writeln!(
+                    f,
+                    "{} (entry {}): {}",
+                    problem.kind,
+                    id_to_string(id),
+                    problem.message
+                )?,
+                None => writeln!(f, "{}: {}", problem.kind, problem.message)?,
+            }
+        }
+        write!(
+            f,
+            "checked {} entries, found {} problems",
+            self.entries,
+            self.problems.len()
+        )
+    }
+}
@@ -1,9 +1,18 @@
-use std::io::BufReader;
+use std::fmt::Display;
+use std::io::{BufReader, BufWriter};
 use std::path::PathBuf;
 
-use crate::lsif::RootMapping;
-use clap::{arg, Parser, Subcommand};
-use tracing::{info};
+use crate::lsif::validate::LocalRoot;
+use crate::lsif::Roots;
+use clap::{Parser, Subcommand, ValueEnum};
+use either::Either;
+use eyre::{eyre, OptionExt};
+use gix::ObjectId;
+use lsp_types::Url;
+use serde::Serialize;
+use shatterbird_storage::model::Upload;
+use shatterbird_storage::Id;
+use tracing::info;
 use tracing_error::ErrorLayer;
 use tracing_subscriber::layer::SubscriberExt;
 use tracing_subscriber::util::SubscriberInitExt;
@@ -12,11 +21,14 @@ use tracing_subscriber::{Layer, Registry};
 mod exclusive;
 mod git;
 mod lsif;
+mod scip;
+mod uploads;
 
 #[derive(Parser, Debug)]
 struct Args {
+    /// Required by all commands except `validate`.
     #[arg(long)]
-    db_url: String,
+    db_url: Option<String>,
 
     #[command(subcommand)]
     command: Command,
@@ -24,12 +36,55 @@ struct Args {
 
 #[derive(Subcommand, Debug)]
 enum Command {
+    /// Checks LSIF dumps without touching the database.
+    Validate {
+        #[arg(long = "input", required = true)]
+        inputs: Vec<PathBuf>,
+
+        /// Local checkouts as `uri-prefix=dir`, to check that ranges fit into files.
+        #[arg(long)]
+        roots: Vec<LocalRoot>,
+
+        #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+        format: OutputFormat,
+    },
+    #[command(flatten)]
+    Db(DbCommand),
+}
+
+/// Commands which need `--db-url`
+#[derive(Subcommand, Debug)]
+enum DbCommand {
     Lsif {
+        /// LSIF dumps to load, `-` for stdin. Gzip and zstd compression are detected automatically.
+        #[arg(long = "input", required = true)]
+        inputs: Vec<PathBuf>,
+
+        #[command(flatten)]
+        roots: Roots,
+
+        #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+        format: OutputFormat,
+
+        #[clap(
+            long,
+            default_missing_value("true"),
+            default_value("false"),
+            num_args(0..=1),
+            require_equals(true),
+            action = clap::ArgAction::Set,
+        )]
+        save: bool,
+    },
+    Scip {
         #[arg(long)]
         input: PathBuf,
 
-        #[arg(long, required = true)]
-        roots: Vec<RootMapping>,
+        #[command(flatten)]
+        roots: Roots,
+
+        #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+        format: OutputFormat,
 
         #[clap(
             long,
@@ -49,6 +104,48 @@ enum Command {
         #[arg(default_value = "10")]
         max_depth: u32,
     },
+    /// Migrates data of previous versions and creates indexes the server relies on.
+    Migrate,
+    Uploads {
+        #[command(subcommand)]
+        command: UploadsCommand,
+    },
+    /// Writes stored uploads back as an LSIF dump.
+    Export {
+        /// Export all ready uploads of this commit.
+        #[arg(long, required_unless_present = "upload", conflicts_with = "upload")]
+        commit: Option<ObjectId>,
+
+        /// Export a single upload.
+        #[arg(long)]
+        upload: Option<Id<Upload>>,
+
+        /// Prefix for document URIs instead of `bird:///<commit>/`.
+        #[arg(long)]
+        uri_prefix: Option<Url>,
+
+        /// Output file, `-` for stdout.
+        #[arg(long, default_value = "-")]
+        output: PathBuf,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum UploadsCommand {
+    List,
+    /// Deletes the upload together with its vertices, edges and ranges.
+    Delete {
+        id: Id<Upload>,
+    },
+    /// Clears document symbols stored with ids of the original dump by older indexer versions.
+    /// `migrate` only lists such uploads, re-importing them keeps the symbols.
+    DropUnresolvedSymbols,
+}
+
+#[derive(ValueEnum, Debug, Copy, Clone)]
+enum OutputFormat {
+    Text,
+    Json,
 }
 
 #[tokio::main(flavor = "current_thread")]
@@ -64,25 +161,97 @@ async fn main() -> eyre::Result<()> {
     color_eyre::install()?;
 
     let args = Args::parse();
-    let storage = shatterbird_storage::Storage::connect(&args.db_url).await?;
-
     info!("running command {:?}", args.command);
-    match args.command {
-        Command::Lsif { input, roots, save } => match input.as_os_str().as_encoded_bytes() {
-            b"-" => {
-                let stdin = BufReader::new(std::io::stdin());
-                lsif::load_lsif(&storage, stdin, roots, save).await?;
+
+    let command = match args.command {
+        Command::Validate {
+            inputs,
+            roots,
+            format,
+        } => {
+            let report = lsif::validate::validate(&inputs, &roots)?;
+            print_report(&report, format)?;
+            if !report.problems.is_empty() {
+                return Err(eyre!("found {} problems", report.problems.len()));
             }
-            _ => {
-                let file = BufReader::new(std::fs::File::open(input)?);
-                lsif::load_lsif(&storage, file, roots, save).await?;
+            return Ok(());
+        }
+        Command::Db(x) => x,
+    };
+
+    let db_url = args.db_url.ok_or_eyre("--db-url is required")?;
+    let storage = shatterbird_storage::Storage::connect(&db_url).await?;
+
+    match command {
+        DbCommand::Lsif {
+            inputs,
+            roots,
+            save,
+            format,
+        } => {
+            let report = lsif::load_lsif(&storage, &inputs, &roots, save).await?;
+            print_report(&report, format)?;
+        }
+        DbCommand::Scip {
+            input,
+            roots,
+            save,
+            format,
+        } => {
+            let report = match input.as_os_str().as_encoded_bytes() {
+                b"-" => scip::load_scip(&storage, std::io::stdin(), &roots, save).await?,
+                _ => {
+                    let file = BufReader::new(std::fs::File::open(input)?);
+                    scip::load_scip(&storage, file, &roots, save).await?
+                }
+            };
+            print_report(&report, format)?;
+        }
+        DbCommand::Git { root, max_depth } => {
+            git::index(&storage, &root, max_depth).await?;
+        }
+        DbCommand::Migrate => {
+            shatterbird_storage::migrations::run(&storage).await?;
+        }
+        DbCommand::Uploads { command } => match command {
+            UploadsCommand::List => uploads::list(&storage).await?,
+            UploadsCommand::Delete { id } => uploads::delete(&storage, id).await?,
+            UploadsCommand::DropUnresolvedSymbols => {
+                uploads::drop_unresolved_symbols(&storage).await?
             }
         },
-        Command::Git { root, max_depth } => {
-            git::index(&storage, &root, max_depth).await?;
+        DbCommand::Export {
+            commit,
+            upload,
+            uri_prefix,
+            output,
+        } => {
+            let source = match (commit, upload) {
+                (Some(oid), _) => Either::Left(oid),
+                (None, Some(id)) => Either::Right(id),
+                (None, None) => unreachable!("enforced by clap"),
+            };
+            match output.as_os_str().as_encoded_bytes() {
+                b"-" => {
+                    let out = BufWriter::new(std::io::stdout().lock());
+                    lsif::export::export_lsif(&storage, source, uri_prefix, out).await?
+                }
+                _ => {
+                    let out = BufWriter::new(std::fs::File::create(output)?);
+                    lsif::export::export_lsif(&storage, source, uri_prefix, out).await?
+                }
+            }
         }
     }
 
     storage.shutdown().await?;
     Ok(())
 }
+
+fn print_report(report: &(impl Display + Serialize), format: OutputFormat) -> eyre::Result<()> {
+    match format {
+        OutputFormat::Text => println!("{report}"),
+        OutputFormat::Json => println!("{}", serde_json::to_string_pretty(report)?),
+    }
+    Ok(())
+}
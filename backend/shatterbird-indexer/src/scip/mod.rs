@@ -0,0 +1,699 @@
+//! Loads SCIP indexes by converting them into the same graph that LSIF dumps produce.
+//!
+//! Based on <https://github.com/sourcegraph/scip/blob/main/bindings/go/scip/convert.go>
+
+use std::collections::{BTreeMap, HashMap};
+use std::io::Read;
+
+use ::scip::types::{Document, Index, PositionEncoding, SymbolInformation, SymbolRole};
+use lsp_types::lsif::{
+    self, EdgeData, EdgeDataMultiIn, Element, Encoding, Entry, ItemKind, MetaData, ToolInfo,
+};
+use lsp_types::{
+    Hover, HoverContents, MarkupContent, MarkupKind, Moniker, MonikerKind, Position,
+    UniquenessLevel, Url,
+};
+use protobuf::Message;
+use tracing::{info, instrument, warn};
+
+use crate::lsif::{ImportReport, Roots};
+use shatterbird_storage::Storage;
+
+#[instrument(skip_all)]
+pub async fn load_scip<R: Read>(
+    storage: &Storage,
+    mut input: R,
+    roots: &Roots,
+    save: bool,
+) -> eyre::Result<ImportReport> {
+    info!("decoding index");
+    let index = Index::parse_from_reader(&mut input)?;
+
+    info!("emitting graph for {} documents", index.documents.len());
+    let entries = Emitter::default().emit(index)?;
+    crate::lsif::load_entries(storage, entries.into_iter().map(Ok), roots, save).await
+}
+
+type Occurrences = Vec<(lsif::Id, lsif::Id)>;
+
+#[derive(Default)]
+struct Symbol {
+    /// Relative path of the document for local symbols, which are unique only inside it
+    scope: Option<String>,
+    result_set: Option<lsif::Id>,
+    info: Option<SymbolInformation>,
+    /// Pairs of document and range ids
+    definitions: Occurrences,
+    references: Occurrences,
+}
+
+#[derive(Default)]
+struct Emitter {
+    next_id: i32,
+    entries: Vec<Entry>,
+    symbols: BTreeMap<String, Symbol>,
+    packages: HashMap<(String, String, String), lsif::Id>,
+}
+
+impl Emitter {
+    fn emit(mut self, index: Index) -> eyre::Result<Vec<Entry>> {
+        let mut project_root = index.metadata.project_root.clone();
+        if !project_root.ends_with('/') {
+            project_root.push('/');
+        }
+        let project_root: Url = project_root
+            .parse()
+            .map_err(|e| eyre::eyre!("invalid project root {}: {}", project_root, e))?;
+
+        let position_encoding = position_encoding(&index.documents);
+        let tool_info = index.metadata.tool_info.as_ref().map(|x| ToolInfo {
+            name: x.name.clone(),
+            args: x.arguments.clone(),
+            version: Some(x.version.clone()).filter(|x| !x.is_empty()),
+        });
+        self.vertex(lsif::Vertex::MetaData(MetaData {
+            version: "0.6.0".to_string(),
+            project_root: None,
+            position_encoding,
+            tool_info,
+        }));
+        self.vertex(lsif::Vertex::Source(lsif::Source {
+            workspace_root: Some(project_root.clone()),
+            repository: None,
+        }));
+        // Documents without a language belong to the language the indexer was written for
+        let tool_language = index
+            .metadata
+            .tool_info
+            .as_ref()
+            .map(|x| x.name.to_lowercase())
+            .map(|x| x.strip_prefix("scip-").map(str::to_string).unwrap_or(x))
+            .filter(|x| !x.is_empty())
+            .unwrap_or_else(|| "unknown".to_string());
+
+        for info in index.external_symbols {
+            let symbol = info.symbol.clone();
+            self.symbol(None, &symbol).info = Some(info);
+        }
+        // SCIP indexes may mix languages, each of them becomes a project of its own
+        let mut projects = BTreeMap::<String, Vec<lsif::Id>>::new();
+        for document in index.documents {
+            let language = Some(document.language.to_lowercase())
+                .filter(|x| !x.is_empty())
+                .unwrap_or_else(|| tool_language.clone());
+            let document = self.document(&project_root, position_encoding, document)?;
+            projects.entry(language).or_default().push(document);
+        }
+        for (kind, documents) in projects {
+            let project = self.vertex(lsif::Vertex::Project(lsif::Project {
+                name: None,
+                resource: Some(project_root.clone()),
+                content: None,
+                kind,
+            }));
+            self.edge(lsif::Edge::Contains(EdgeDataMultiIn {
+                in_vs: documents,
+                out_v: project,
+            }));
+        }
+
+        self.emit_symbols();
+        Ok(self.entries)
+    }
+
+    fn document(
+        &mut self,
+        root: &Url,
+        encoding: Encoding,
+        document: Document,
+    ) -> eyre::Result<lsif::Id> {
+        let uri = root
+            .join(&document.relative_path)
+            .map_err(|e| eyre::eyre!("invalid path {}: {}", document.relative_path, e))?;
+        // LSIF has no UTF-32 positions, so they are converted with the text of the document
+        let utf32 = document.position_encoding.enum_value()
+            == Ok(PositionEncoding::UTF32CodeUnitOffsetFromLineStart);
+        if utf32 && document.text.is_empty() {
+            return Err(eyre::eyre!(
+                "document {} has UTF-32 positions but no text to convert them",
+                document.relative_path
+            ));
+        }
+        let lines = document
+            .text
+            .split('\n')
+            .map(|x| x.strip_suffix('\r').unwrap_or(x))
+            .collect::<Vec<_>>();
+        let doc_id = self.vertex(lsif::Vertex::Document(lsif::Document {
+            uri,
+            language_id: Some(document.language.to_lowercase()).filter(|x| !x.is_empty()),
+            contents: None,
+        }));
+        let scope = document.relative_path.as_str();
+
+        for info in document.symbols {
+            let symbol = info.symbol.clone();
+            self.symbol(Some(scope), &symbol).info = Some(info);
+        }
+
+        let mut ranges = Vec::new();
+        for occurrence in document.occurrences {
+            let range = match parse_range(&occurrence.range) {
+                Some(x) if utf32 => lsp_types::Range {
+                    start: from_utf32(&lines, x.start, encoding),
+                    end: from_utf32(&lines, x.end, encoding),
+                },
+                Some(x) => x,
+                None => {
+                    warn!(
+                        "invalid range {:?} in {}, skipping",
+                        occurrence.range, document.relative_path
+                    );
+                    continue;
+                }
+            };
+            let range_id = self.vertex(lsif::Vertex::Range { range, tag: None });
+            ranges.push(range_id.clone());
+
+            if !occurrence.override_documentation.is_empty() {
+                let hover = self.hover(occurrence.override_documentation.join("\n\n---\n\n"));
+                self.edge(lsif::Edge::Hover(EdgeData {
+                    in_v: hover,
+                    out_v: range_id.clone(),
+                }));
+            }
+            if occurrence.symbol.is_empty() {
+                continue;
+            }
+
+            let existing = self
+                .symbol(Some(scope), &occurrence.symbol)
+                .result_set
+                .clone();
+            let result_set = match existing {
+                Some(x) => x,
+                None => {
+                    let id = self.vertex(lsif::Vertex::ResultSet(lsif::ResultSet { key: None }));
+                    self.symbol(Some(scope), &occurrence.symbol).result_set = Some(id.clone());
+                    id
+                }
+            };
+            self.edge(lsif::Edge::Next(EdgeData {
+                in_v: result_set,
+                out_v: range_id.clone(),
+            }));
+
+            let symbol = self.symbol(Some(scope), &occurrence.symbol);
+            if occurrence.symbol_roles & SymbolRole::Definition as i32 != 0 {
+                symbol.definitions.push((doc_id.clone(), range_id));
+            } else {
+                symbol.references.push((doc_id.clone(), range_id));
+            }
+        }
+
+        if !ranges.is_empty() {
+            self.edge(lsif::Edge::Contains(EdgeDataMultiIn {
+                in_vs: ranges,
+                out_v: doc_id.clone(),
+            }));
+        }
+        Ok(doc_id)
+    }
+
+    fn emit_symbols(&mut self) {
+        let symbols = std::mem::take(&mut self.symbols);
+
+        // Relationships point from a symbol to the ones it implements, references or defines,
+        // so results of the related symbols have to be collected before anything is emitted
+        let mut implementations = HashMap::<&str, Occurrences>::new();
+        let mut type_definitions = HashMap::<&str, Occurrences>::new();
+        let mut extra_definitions = HashMap::<&str, Occurrences>::new();
+        let mut linked_references = HashMap::<&str, Vec<&str>>::new();
+        for (key, symbol) in &symbols {
+            let relationships = symbol.info.iter().flat_map(|x| &x.relationships);
+            for relationship in relationships {
+                let related = symbol_key(symbol.scope.as_deref(), &relationship.symbol);
+                let related = match symbols.get_key_value(&related) {
+                    Some((k, v)) => (k.as_str(), v),
+                    None => continue,
+                };
+                if relationship.is_implementation {
+                    implementations
+                        .entry(related.0)
+                        .or_default()
+                        .extend(symbol.definitions.iter().cloned());
+                }
+                if relationship.is_type_definition {
+                    type_definitions
+                        .entry(key.as_str())
+                        .or_default()
+                        .extend(related.1.definitions.iter().cloned());
+                }
+                if relationship.is_definition {
+                    extra_definitions
+                        .entry(key.as_str())
+                        .or_default()
+                        .extend(related.1.definitions.iter().cloned());
+                }
+                if relationship.is_reference {
+                    linked_references
+                        .entry(key.as_str())
+                        .or_default()
+                        .push(related.0);
+                }
+            }
+        }
+
+        let reference_results = symbols
+            .iter()
+            .filter(|(_, symbol)| symbol.result_set.is_some())
+            .map(|(key, _)| (key.as_str(), self.vertex(lsif::Vertex::ReferenceResult)))
+            .collect::<HashMap<_, _>>();
+
+        for (key, symbol) in &symbols {
+            let result_set = match &symbol.result_set {
+                Some(x) => x.clone(),
+                None => continue,
+            };
+
+            if let Some(text) = symbol.info.as_ref().and_then(hover_text) {
+                let hover = self.hover(text);
+                self.edge(lsif::Edge::Hover(EdgeData {
+                    in_v: hover,
+                    out_v: result_set.clone(),
+                }));
+            }
+
+            let mut definitions = symbol.definitions.clone();
+            definitions.extend(extra_definitions.remove(key.as_str()).unwrap_or_default());
+            if !definitions.is_empty() {
+                let result = self.vertex(lsif::Vertex::DefinitionResult);
+                self.edge(lsif::Edge::Definition(EdgeData {
+                    in_v: result.clone(),
+                    out_v: result_set.clone(),
+                }));
+                self.items(&result, &definitions, None);
+            }
+
+            let references = reference_results[key.as_str()].clone();
+            self.edge(lsif::Edge::References(EdgeData {
+                in_v: references.clone(),
+                out_v: result_set.clone(),
+            }));
+            self.items(
+                &references,
+                &symbol.definitions,
+                Some(ItemKind::Definitions),
+            );
+            self.items(&references, &symbol.references, Some(ItemKind::References));
+            let document = symbol
+                .definitions
+                .iter()
+                .chain(&symbol.references)
+                .map(|(doc, _)| doc.clone())
+                .next();
+            let linked = linked_references.remove(key.as_str()).unwrap_or_default();
+            if let Some(document) = document {
+                let in_vs = linked
+                    .iter()
+                    .filter_map(|x| reference_results.get(x).cloned())
+                    .collect::<Vec<_>>();
+                if !in_vs.is_empty() {
+                    self.edge(lsif::Edge::Item(lsif::Item {
+                        document,
+                        property: Some(ItemKind::ReferenceResults),
+                        edge_data: EdgeDataMultiIn {
+                            in_vs,
+                            out_v: references,
+                        },
+                    }));
+                }
+            }
+
+            if let Some(found) = implementations.remove(key.as_str()) {
+                let result = self.vertex(lsif::Vertex::ImplementationResult);
+                self.edge(lsif::Edge::Implementation(EdgeData {
+                    in_v: result.clone(),
+                    out_v: result_set.clone(),
+                }));
+                self.items(&result, &found, None);
+            }
+            if let Some(found) = type_definitions.remove(key.as_str()) {
+                let result = self.vertex(lsif::Vertex::TypeDefinitionResult);
+                self.edge(lsif::Edge::TypeDefinition(EdgeData {
+                    in_v: result.clone(),
+                    out_v: result_set.clone(),
+                }));
+                self.items(&result, &found, None);
+            }
+
+            if symbol.scope.is_none() {
+                self.moniker(key, &result_set, !symbol.definitions.is_empty());
+            }
+        }
+    }
+
+    fn moniker(&mut self, symbol: &str, result_set: &lsif::Id, defined: bool) {
+        let parsed = match ::scip::symbol::parse_symbol(symbol) {
+            Ok(x) => x,
+            Err(e) => {
+                warn!("failed to parse symbol {}: {:?}", symbol, e);
+                return;
+            }
+        };
+        let moniker = self.vertex(lsif::Vertex::Moniker(Moniker {
+            scheme: parsed.scheme.clone(),
+            identifier: symbol.to_string(),
+            unique: Some(UniquenessLevel::Scheme),
+            kind: Some(if defined {
+                MonikerKind::Export
+            } else {
+                MonikerKind::Import
+            }),
+        }));
+        self.edge(lsif::Edge::Moniker(EdgeData {
+            in_v: moniker.clone(),
+            out_v: result_set.clone(),
+        }));
+
+        let package = match parsed.package.as_ref() {
+            Some(x) if !x.name.is_empty() => x,
+            _ => return,
+        };
+        let key = (
+            package.manager.clone(),
+            package.name.clone(),
+            package.version.clone(),
+        );
+        let info = match self.packages.get(&key) {
+            Some(x) => x.clone(),
+            None => {
+                let id = self.vertex(lsif::Vertex::PackageInformation(lsif::PackageInformation {
+                    name: package.name.clone(),
+                    manager: package.manager.clone(),
+                    uri: None,
+                    content: None,
+                    repository: None,
+                    version: Some(package.version.clone()).filter(|x| !x.is_empty()),
+                }));
+                self.packages.insert(key, id.clone());
+                id
+            }
+        };
+        self.edge(lsif::Edge::PackageInformation(EdgeData {
+            in_v: info,
+            out_v: moniker,
+        }));
+    }
+
+    fn items(&mut self, result: &lsif::Id, ranges: &Occurrences, property: Option<ItemKind>) {
+        let mut by_document = Vec::<(lsif::Id, Vec<lsif::Id>)>::new();
+        for (doc, range) in ranges {
+            match by_document.iter_mut().find(|(x, _)| x == doc) {
+                Some((_, in_vs)) => in_vs.push(range.clone()),
+                None => by_document.push((doc.clone(), vec![range.clone()])),
+            }
+        }
+        for (document, in_vs) in by_document {
+            self.edge(lsif::Edge::Item(lsif::Item {
+                document,
+                property: property.clone(),
+                edge_data: EdgeDataMultiIn {
+                    in_vs,
+                    out_v: result.clone(),
+                },
+            }));
+        }
+    }
+
+    fn hover(&mut self, text: String) -> lsif::Id {
+        self.vertex(lsif::Vertex::HoverResult {
+            result: Hover {
+                contents: HoverContents::Markup(MarkupContent {
+                    kind: MarkupKind::Markdown,
+                    value: text,
+                }),
+                range: None,
+            },
+        })
+    }
+
+    fn symbol(&mut self, scope: Option<&str>, symbol: &str) -> &mut Symbol {
+        let scope = scope.filter(|_| ::scip::symbol::is_local_symbol(symbol));
+        self.symbols
+            .entry(symbol_key(scope, symbol))
+            .or_insert_with(|| Symbol {
+                scope: scope.map(str::to_string),
+                ..Symbol::default()
+            })
+    }
+
+    fn vertex(&mut self, vertex: lsif::Vertex) -> lsif::Id {
+        self.push(Element::Vertex(vertex))
+    }
+
+    fn edge(&mut self, edge: lsif::Edge) -> lsif::Id {
+        self.push(Element::Edge(edge))
+    }
+
+    fn push(&mut self, data: Element) -> lsif::Id {
+        self.next_id += 1;
+        let id = lsif::Id::Number(self.next_id);
+        self.entries.push(Entry {
+            id: id.clone(),
+            data,
+        });
+        id
+    }
+}
+
+/// Local symbols are unique only inside their document, so it becomes a part of the key
+fn symbol_key(scope: Option<&str>, symbol: &str) -> String {
+    match scope {
+        Some(path) if ::scip::symbol::is_local_symbol(symbol) => format!("{path}#{symbol}"),
+        _ => symbol.to_string(),
+    }
+}
+
+fn hover_text(info: &SymbolInformation) -> Option<String> {
+    let mut parts = Vec::new();
+    if let Some(signature) = info.signature_documentation.as_ref() {
+        if !signature.text.is_empty() {
+            parts.push(format!(
+                "```{}\n{}\n```",
+                signature.language.to_lowercase(),
+                signature.text
+            ));
+        }
+    }
+    parts.extend(info.documentation.iter().cloned());
+    if parts.is_empty() {
+        return None;
+    }
+    Some(parts.join("\n\n---\n\n"))
+}
+
+/// SCIP ranges are either `[startLine, startCharacter, endCharacter]`
+/// or `[startLine, startCharacter, endLine, endCharacter]`
+fn parse_range(range: &[i32]) -> Option<lsp_types::Range> {
+    let range = range
+        .iter()
+        .map(|&x| u32::try_from(x).ok())
+        .collect::<Option<Vec<_>>>()?;
+    let (start, end) = match range[..] {
+        [line, start, end] => (Position::new(line, start), Position::new(line, end)),
+        [start_line, start, end_line, end] => (
+            Position::new(start_line, start),
+            Position::new(end_line, end),
+        ),
+        _ => return None,
+    };
+    Some(lsp_types::Range { start, end })
+}
+
+/// Converts a position which counts characters into `encoding` using lines of its document.
+/// Characters past the end of the line are kept as they are.
+fn from_utf32(lines: &[&str], position: Position, encoding: Encoding) -> Position {
+    let line = lines
+        .get(position.line as usize)
+        .copied()
+        .unwrap_or_default();
+    let mut character = 0;
+    let mut chars = line.chars();
+    for _ in 0..position.character {
+        character += match chars.next() {
+            Some(ch) if encoding == Encoding::Utf8 => ch.len_utf8() as u32,
+            Some(ch) => ch.len_utf16() as u32,
+            None => 1,
+        };
+    }
+    Position::new(position.line, character)
+}
+
+/// LSIF declares a single encoding for the whole dump, while SCIP does it for every document.
+/// UTF-32 positions are converted into the encoding of the dump, which is UTF-16 if nothing else.
+fn position_encoding(documents: &[Document]) -> Encoding {
+    let mut result = None;
+    for document in documents {
+        let encoding = match document.position_encoding.enum_value() {
+            Ok(PositionEncoding::UTF8CodeUnitOffsetFromLineStart) => Encoding::Utf8,
+            Ok(PositionEncoding::UTF16CodeUnitOffsetFromLineStart) => Encoding::Utf16,
+            Ok(PositionEncoding::UTF32CodeUnitOffsetFromLineStart)
+            | Ok(PositionEncoding::UnspecifiedPositionEncoding)
+            | Err(_) => continue,
+        };
+        match result {
+            None => result = Some(encoding),
+            Some(x) if x != encoding => warn!(
+                "document {} uses {:?} positions while the index uses {:?}",
+                document.relative_path, encoding, x
+            ),
+            Some(_) => {}
+        }
+    }
+    result.unwrap_or(Encoding::Utf16)
+}
+
+#[cfg(test)]
+mod tests {
+    use ::scip::types::{Metadata, Occurrence, ToolInfo as ScipToolInfo};
+    use protobuf::MessageField;
+
+    use super::*;
+
+    fn document(path: &str, language: &str, encoding: PositionEncoding) -> Document {
+        let mut document = Document::new();
+        document.relative_path = path.to_string();
+        document.language = language.to_string();
+        document.position_encoding = encoding.into();
+        document
+    }
+
+    fn occurrence(range: &[i32], symbol: &str, roles: i32) -> Occurrence {
+        let mut occurrence = Occurrence::new();
+        occurrence.range = range.to_vec();
+        occurrence.symbol = symbol.to_string();
+        occurrence.symbol_roles = roles;
+        occurrence
+    }
+
+    fn fixture() -> Index {
+        let mut tool = ScipToolInfo::new();
+        tool.name = "scip-typescript".to_string();
+        let mut metadata = Metadata::new();
+        metadata.project_root = "file:///repo".to_string();
+        metadata.tool_info = MessageField::some(tool);
+
+        let mut multi_line = document(
+            "src/a.ts",
+            "TypeScript",
+            PositionEncoding::UTF16CodeUnitOffsetFromLineStart,
+        );
+        multi_line.occurrences = vec![
+            occurrence(&[0, 4, 2, 1], "local 0", SymbolRole::Definition as i32),
+            occurrence(&[3, 0, 1], "local 0", 0),
+        ];
+        let mut non_ascii = document(
+            "src/ёж.ts",
+            "",
+            PositionEncoding::UTF32CodeUnitOffsetFromLineStart,
+        );
+        non_ascii.text = "# ёж 😀x\r\nlet x = 1;\n".to_string();
+        non_ascii.occurrences = vec![
+            occurrence(&[0, 5, 7], "local 0", SymbolRole::Definition as i32),
+            occurrence(&[1, 4, 5], "local 0", 0),
+        ];
+        let python = document(
+            "main.py",
+            "Python",
+            PositionEncoding::UTF8CodeUnitOffsetFromLineStart,
+        );
+
+        let mut index = Index::new();
+        index.metadata = MessageField::some(metadata);
+        index.documents = vec![multi_line, non_ascii, python];
+        index
+    }
+
+    fn ranges(entries: &[Entry]) -> Vec<((u32, u32), (u32, u32))> {
+        entries
+            .iter()
+            .filter_map(|x| match &x.data {
+                Element::Vertex(lsif::Vertex::Range { range, .. }) => Some((
+                    (range.start.line, range.start.character),
+                    (range.end.line, range.end.character),
+                )),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn multi_line_and_utf32_ranges() {
+        let entries = Emitter::default().emit(fixture()).unwrap();
+        let encoding = entries.iter().find_map(|x| match &x.data {
+            Element::Vertex(lsif::Vertex::MetaData(meta)) => Some(meta.position_encoding),
+            _ => None,
+        });
+        assert_eq!(encoding, Some(Encoding::Utf16));
+        assert_eq!(
+            ranges(&entries),
+            [
+                ((0, 4), (2, 1)),
+                ((3, 0), (3, 1)),
+                ((0, 5), (0, 8)),
+                ((1, 4), (1, 5)),
+            ]
+        );
+    }
+
+    #[test]
+    fn projects_follow_document_languages() {
+        let entries = Emitter::default().emit(fixture()).unwrap();
+        let documents = entries
+            .iter()
+            .filter_map(|x| match &x.data {
+                Element::Vertex(lsif::Vertex::Document(doc)) => Some((x.id.clone(), doc)),
+                _ => None,
+            })
+            .collect::<HashMap<_, _>>();
+        let mut projects = Vec::new();
+        for entry in &entries {
+            let Element::Vertex(lsif::Vertex::Project(project)) = &entry.data else {
+                continue;
+            };
+            let contained = entries.iter().find_map(|x| match &x.data {
+                Element::Edge(lsif::Edge::Contains(edge)) if edge.out_v == entry.id => {
+                    Some(&edge.in_vs)
+                }
+                _ => None,
+            });
+            let paths = contained
+                .into_iter()
+                .flatten()
+                .map(|x| documents[x].uri.path().to_string())
+                .collect::<Vec<_>>();
+            projects.push((project.kind.clone(), paths));
+        }
+        assert_eq!(
+            projects,
+            [
+                ("python".to_string(), vec!["/repo/main.py".to_string()]),
+                (
+                    "typescript".to_string(),
+                    vec![
+                        "/repo/src/a.ts".to_string(),
+                        "/repo/src/%D1%91%D0%B6.ts".to_string(),
+                    ]
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn utf32_without_text_is_rejected() {
+        let mut index = fixture();
+        index.documents[1].text.clear();
+        assert!(Emitter::default().emit(index).is_err());
+    }
+}
@@ -0,0 +1,53 @@
+use bson::doc;
+use eyre::{eyre, OptionExt};
+use mongodb::options::FindOptions;
+use shatterbird_storage::model::{Commit, Upload};
+use shatterbird_storage::util::uploads;
+use shatterbird_storage::{Id, Storage};
+
+pub async fn list(storage: &Storage) -> eyre::Result<()> {
+    let all = storage
+        .find::<Upload>(
+            None,
+            FindOptions::builder()
+                .sort(doc! { "created_at": 1 })
+                .build(),
+        )
+        .await?;
+    for upload in all {
+        let mut commits = Vec::new();
+        for id in &upload.commits {
+            let commit: Commit = storage
+                .get(*id)
+                .await?
+                .ok_or_eyre(eyre!("commit {} not found in DB", id))?;
+            commits.push(commit.oid.to_string());
+        }
+        println!(
+            "{}\t{:?}\t{}\t{}\t{}",
+            upload.id,
+            upload.state,
+            upload.project,
+            upload.created_at,
+            commits.join(",")
+        );
+    }
+    Ok(())
+}
+
+pub async fn delete(storage: &Storage, id: Id<Upload>) -> eyre::Result<()> {
+    storage
+        .get(id)
+        .await?
+        .ok_or_eyre(eyre!("upload {} not found", id))?;
+    uploads::delete(storage, id).await
+}
+
+pub async fn drop_unresolved_symbols(storage: &Storage) -> eyre::Result<()> {
+    for upload in uploads::with_unresolved_symbols(storage).await? {
+        println!("{}", upload);
+    }
+    let changed = uploads::drop_unresolved_symbols(storage).await?;
+    println!("cleared symbols of {} documents", changed);
+    Ok(())
+}
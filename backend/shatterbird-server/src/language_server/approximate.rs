@@ -0,0 +1,56 @@
+use std::cell::RefCell;
+use std::future::Future;
+
+use axum::http::{HeaderName, HeaderValue};
+use axum::response::{IntoResponse, Response};
+use lsp_types::TextDocumentPositionParams;
+use shatterbird_storage::model::lang::EdgeInfoDiscriminants;
+use shatterbird_storage::model::Commit;
+use shatterbird_storage::util;
+use shatterbird_storage::util::graph::ResolvedPosition;
+
+use crate::language_server::error::LspError;
+use crate::state::ServerState;
+
+/// Set on responses answered from an indexed ancestor instead of the requested commit,
+/// the value is the hash of the ancestor
+pub const HEADER: HeaderName = HeaderName::from_static("x-shatterbird-approximate");
+
+tokio::task_local! {
+    static ANCESTOR: RefCell<Option<String>>;
+}
+
+/// Marks the response of the current request as approximate
+pub fn mark(ancestor: &Commit) {
+    let _ = ANCESTOR.try_with(|x| *x.borrow_mut() = Some(ancestor.oid.to_string()));
+}
+
+/// Runs a request handler, adding [`HEADER`] to its response if any answer was approximate
+pub async fn scope<T: IntoResponse>(
+    handler: impl Future<Output = Result<T, LspError>>,
+) -> Result<Response, LspError> {
+    ANCESTOR
+        .scope(RefCell::new(None), async {
+            let mut response = handler.await?.into_response();
+            let ancestor = ANCESTOR.with(|x| x.take());
+            if let Some(value) = ancestor.and_then(|x| HeaderValue::from_str(&x).ok()) {
+                response.headers_mut().insert(HEADER, value);
+            }
+            Ok(response)
+        })
+        .await
+}
+
+/// Finds what is at the position like [`util::graph::find`], marking the response
+/// as approximate when the commit is not indexed and the answer comes from an ancestor
+pub async fn find(
+    state: &ServerState,
+    edge: Option<EdgeInfoDiscriminants>,
+    position: &TextDocumentPositionParams,
+) -> Result<ResolvedPosition, LspError> {
+    let found = util::graph::find(&state.storage, edge, position).await?;
+    if let Some(ancestor) = &found.ancestor {
+        mark(ancestor);
+    }
+    Ok(found)
+}
@@ -0,0 +1,464 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use eyre::eyre;
+use futures::future::try_join_all;
+use lsp_types::{
+    CallHierarchyIncomingCall, CallHierarchyIncomingCallsParams, CallHierarchyItem,
+    CallHierarchyOutgoingCall, CallHierarchyOutgoingCallsParams, CallHierarchyPrepareParams,
+    DocumentSymbol, Location, Position, Range, SymbolKind, TextDocumentIdentifier,
+    TextDocumentPositionParams, Url,
+};
+use mongodb::bson::doc;
+use tracing::instrument;
+
+use shatterbird_storage::model::lang::{
+    EdgeInfoDiscriminants, VertexInfo, VertexInfoDiscriminants,
+};
+use shatterbird_storage::model::{self, Commit, FileContent, Line, Node, Vertex};
+use shatterbird_storage::util;
+use shatterbird_storage::Id;
+
+use crate::language_server::approximate;
+use crate::language_server::error::LspError;
+use crate::language_server::methods::{dedup_locations, document_outline, find_locations};
+use crate::state::ServerState;
+
+/// Function-like symbol at the definition of the symbol under the cursor
+#[instrument(skip(state), err)]
+pub async fn prepare(
+    state: Arc<ServerState>,
+    req: CallHierarchyPrepareParams,
+) -> Result<Option<Vec<CallHierarchyItem>>, LspError> {
+    let position = &req.text_document_position_params;
+    let mut definitions = dedup_locations(
+        find_locations(
+            &state,
+            position,
+            EdgeInfoDiscriminants::Definition,
+            VertexInfoDiscriminants::DefinitionResult,
+        )
+        .await?,
+    );
+    // The cursor may be at a function which is not referenced by any definition result
+    if definitions.is_empty() {
+        definitions.push(Location {
+            uri: position.text_document.uri.clone(),
+            range: Range::new(position.position, position.position),
+        });
+    }
+
+    let mut outlines = Outlines::new(&state);
+    let mut items = Vec::new();
+    for definition in definitions {
+        let functions = outlines.functions(&definition.uri).await?;
+        let found = functions
+            .iter()
+            .find(|x| contains(&x.selection_range, definition.range.start));
+        if let Some(symbol) = found {
+            items.push(to_item(symbol, &definition.uri));
+        }
+    }
+    if items.is_empty() {
+        return Ok(None);
+    }
+    Ok(Some(items))
+}
+
+/// Functions enclosing references to the item
+#[instrument(skip(state), err)]
+pub async fn incoming_calls(
+    state: Arc<ServerState>,
+    req: CallHierarchyIncomingCallsParams,
+) -> Result<Option<Vec<CallHierarchyIncomingCall>>, LspError> {
+    let item = req.item;
+    let position = TextDocumentPositionParams::new(
+        TextDocumentIdentifier::new(item.uri.clone()),
+        item.selection_range.start,
+    );
+    let references = dedup_locations(
+        find_locations(
+            &state,
+            &position,
+            EdgeInfoDiscriminants::References,
+            VertexInfoDiscriminants::ReferenceResult,
+        )
+        .await?,
+    );
+
+    let mut outlines = Outlines::new(&state);
+    let mut calls = Calls::default();
+    for reference in references {
+        if reference.uri == item.uri && reference.range == item.selection_range {
+            continue;
+        }
+        let functions = outlines.functions(&reference.uri).await?;
+        if let Some(caller) = innermost(functions, reference.range.start) {
+            calls.add(to_item(caller, &reference.uri), reference.range);
+        }
+    }
+    let calls = calls
+        .into_inner()
+        .map(|(from, from_ranges)| CallHierarchyIncomingCall { from, from_ranges })
+        .collect::<Vec<_>>();
+    if calls.is_empty() {
+        return Ok(None);
+    }
+    Ok(Some(calls))
+}
+
+/// Functions defined by the symbols referenced inside the item
+#[instrument(skip(state), err)]
+pub async fn outgoing_calls(
+    state: Arc<ServerState>,
+    req: CallHierarchyOutgoingCallsParams,
+) -> Result<Option<Vec<CallHierarchyOutgoingCall>>, LspError> {
+    let item = req.item;
+    let position = TextDocumentPositionParams::new(
+        TextDocumentIdentifier::new(item.uri.clone()),
+        item.selection_range.start,
+    );
+    // Only the file the item is in is needed, ranges are looked up below
+    let found = approximate::find(&state, None, &position).await?;
+    let translate_to = found.ancestor.as_ref().map(|_| found.commit);
+    let lines = text_lines(&state, found.node).await?;
+    let indexed = match found.indexed_node == found.node {
+        true => lines.clone(),
+        false => text_lines(&state, found.indexed_node).await?,
+    };
+    let searchable = util::uploads::searchable(&state.storage).await?;
+    let ranges = state
+        .storage
+        .find::<model::Range>(
+            doc! { "path": found.indexed_node, "upload": searchable },
+            None,
+        )
+        .await?
+        .into_iter()
+        .filter_map(|range| {
+            let (start, end) = util::graph::line_span(&indexed, &range)?;
+            let (start, end) = match translate_to {
+                Some(_) => (
+                    util::graph::translate_line(&indexed, start, &lines)?,
+                    util::graph::translate_line(&indexed, end, &lines)?,
+                ),
+                None => (start, end),
+            };
+            let position = Range::new(
+                Position::new(start as u32, range.start),
+                Position::new(end as u32, range.end),
+            );
+            let inside = item.range.start <= position.start && position.end <= item.range.end;
+            (inside && position != item.selection_range).then_some((range.id, position))
+        })
+        .collect::<Vec<_>>();
+
+    let ids = ranges.iter().map(|(x, _)| *x).collect::<Vec<_>>();
+    let mut definitions = definitions(&state, &ids, translate_to).await?;
+    let mut outlines = Outlines::new(&state);
+    let mut calls = Calls::default();
+    for (range, position) in ranges {
+        for definition in definitions.remove(&range).unwrap_or_default() {
+            if definition.uri == item.uri && definition.range == position {
+                continue;
+            }
+            let functions = outlines.functions(&definition.uri).await?;
+            let callee = functions
+                .iter()
+                .find(|x| contains(&x.selection_range, definition.range.start));
+            if let Some(callee) = callee {
+                calls.add(to_item(callee, &definition.uri), position);
+            }
+        }
+    }
+    let calls = calls
+        .into_inner()
+        .map(|(to, from_ranges)| CallHierarchyOutgoingCall { to, from_ranges })
+        .collect::<Vec<_>>();
+    if calls.is_empty() {
+        return Ok(None);
+    }
+    Ok(Some(calls))
+}
+
+async fn text_lines(state: &ServerState, node: Id<Node>) -> Result<Vec<Id<Line>>, LspError> {
+    match state.storage.get(node).await? {
+        Some(Node {
+            content: FileContent::Text { lines, .. },
+            ..
+        }) => Ok(lines),
+        _ => Err(LspError::bad_request(eyre!("not a text file"))),
+    }
+}
+
+/// Definitions of ranges, precomputed or found by walking the graph, with a few queries
+/// for all of them. Locations found through an indexed ancestor are translated to `translate_to`.
+async fn definitions(
+    state: &ServerState,
+    ranges: &[Id<model::Range>],
+    translate_to: Option<Id<Commit>>,
+) -> eyre::Result<HashMap<Id<model::Range>, Vec<Location>>> {
+    let navigation = util::graph::find_navigation(&state.storage, ranges).await?;
+    let mut precomputed = navigation
+        .into_iter()
+        .map(|(range, x)| (range, x.definitions))
+        .collect::<HashMap<_, _>>();
+    let mut targets = HashMap::<_, Vec<_>>::new();
+
+    let rest = ranges
+        .iter()
+        .filter(|x| !precomputed.contains_key(x))
+        .collect::<Vec<_>>();
+    let vertices = state
+        .storage
+        .find::<Vertex>(
+            doc! {
+                "data.vertex": <&str>::from(VertexInfoDiscriminants::Range),
+                "data.range": { "$in": rest },
+            },
+            None,
+        )
+        .await?
+        .into_iter()
+        .filter_map(|x| match x.data {
+            VertexInfo::Range { range, .. } => Some((x.id, range)),
+            _ => None,
+        })
+        .collect::<HashMap<_, _>>();
+    let starts = vertices.keys().copied().collect::<Vec<_>>();
+    let found = util::graph::follow_many(
+        &state.storage,
+        &starts,
+        EdgeInfoDiscriminants::Definition.into(),
+    )
+    .await?;
+    let results = found
+        .into_iter()
+        .map(|(start, found)| {
+            let results =
+                util::graph::filter_vertices(found, VertexInfoDiscriminants::DefinitionResult);
+            (vertices[&start], results.map(|x| x.id).collect::<Vec<_>>())
+        })
+        .collect::<Vec<_>>();
+    let all = results
+        .iter()
+        .flat_map(|(_, x)| x)
+        .copied()
+        .collect::<Vec<_>>();
+    // Many references of a symbol share its definition result
+    let items = util::graph::find_items_by_result(&state.storage, &all).await?;
+    for (range, results) in results {
+        let found = results
+            .iter()
+            .filter_map(|x| items.get(x))
+            .flatten()
+            .cloned();
+        targets.entry(range).or_default().extend(found);
+    }
+
+    // Precomputed locations are relative to the upload's commit, so translating them needs ranges
+    if translate_to.is_some() {
+        let ids = precomputed
+            .values()
+            .flatten()
+            .map(|x| x.range)
+            .collect::<Vec<_>>();
+        let loaded = state
+            .storage
+            .find::<model::Range>(doc! { "_id": { "$in": ids } }, None)
+            .await?
+            .into_iter()
+            .map(|x| (x.id, x))
+            .collect::<HashMap<_, _>>();
+        for (range, definitions) in precomputed.drain() {
+            let found = definitions
+                .iter()
+                .filter_map(|x| loaded.get(&x.range).cloned());
+            targets.entry(range).or_default().extend(found);
+        }
+    }
+
+    let mut result = precomputed
+        .into_iter()
+        .map(|(range, definitions)| (range, definitions.into_iter().map(|x| x.location).collect()))
+        .collect::<HashMap<_, Vec<_>>>();
+    let unique = targets
+        .values()
+        .flatten()
+        .map(|x| (x.id, x))
+        .collect::<HashMap<_, _>>();
+    let locations = try_join_all(unique.into_values().map(|x| async move {
+        let location = match translate_to {
+            Some(commit) => util::graph::to_location_in(&state.storage, x, commit).await?,
+            None => Some(util::graph::to_location(&state.storage, x).await?),
+        };
+        eyre::Ok((x.id, location))
+    }))
+    .await?
+    .into_iter()
+    .filter_map(|(id, location)| Some((id, location?)))
+    .collect::<HashMap<_, _>>();
+    for (range, found) in targets {
+        let found = found.iter().filter_map(|x| locations.get(&x.id).cloned());
+        result.entry(range).or_default().extend(found);
+    }
+    Ok(result)
+}
+
+/// Function-like symbols of documents, loaded once per request
+struct Outlines<'a> {
+    state: &'a ServerState,
+    functions: HashMap<Url, Vec<DocumentSymbol>>,
+}
+
+impl<'a> Outlines<'a> {
+    fn new(state: &'a ServerState) -> Self {
+        Outlines {
+            state,
+            functions: HashMap::new(),
+        }
+    }
+
+    async fn functions(&mut self, uri: &Url) -> Result<&[DocumentSymbol], LspError> {
+        if !self.functions.contains_key(uri) {
+            let symbols = document_outline(self.state, uri).await?.unwrap_or_default();
+            let mut functions = Vec::new();
+            flatten_functions(symbols, &mut functions);
+            self.functions.insert(uri.clone(), functions);
+        }
+        Ok(&self.functions[uri])
+    }
+}
+
+fn flatten_functions(symbols: Vec<DocumentSymbol>, result: &mut Vec<DocumentSymbol>) {
+    for mut symbol in symbols {
+        flatten_functions(symbol.children.take().unwrap_or_default(), result);
+        if matches!(
+            symbol.kind,
+            SymbolKind::FUNCTION | SymbolKind::METHOD | SymbolKind::CONSTRUCTOR
+        ) {
+            result.push(symbol);
+        }
+    }
+}
+
+/// Calls grouped by the item on the other side, in the order they were found
+#[derive(Default)]
+struct Calls {
+    items: Vec<(CallHierarchyItem, Vec<Range>)>,
+}
+
+impl Calls {
+    fn add(&mut self, item: CallHierarchyItem, range: Range) {
+        let existing = self
+            .items
+            .iter_mut()
+            .find(|(x, _)| x.uri == item.uri && x.selection_range == item.selection_range);
+        match existing {
+            Some((_, ranges)) if ranges.contains(&range) => {}
+            Some((_, ranges)) => ranges.push(range),
+            None => self.items.push((item, vec![range])),
+        }
+    }
+
+    fn into_inner(self) -> impl Iterator<Item = (CallHierarchyItem, Vec<Range>)> {
+        self.items.into_iter()
+    }
+}
+
+fn contains(range: &Range, position: Position) -> bool {
+    range.start <= position && position <= range.end
+}
+
+/// Nested functions start later than the ones enclosing them
+fn innermost(functions: &[DocumentSymbol], position: Position) -> Option<&DocumentSymbol> {
+    functions
+        .iter()
+        .filter(|x| contains(&x.range, position))
+        .max_by_key(|x| x.range.start)
+}
+
+fn to_item(symbol: &DocumentSymbol, uri: &Url) -> CallHierarchyItem {
+    CallHierarchyItem {
+        name: symbol.name.clone(),
+        kind: symbol.kind,
+        tags: symbol.tags.clone(),
+        detail: symbol.detail.clone(),
+        uri: uri.clone(),
+        range: symbol.range,
+        selection_range: symbol.selection_range,
+        data: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn function(name: &str, start: u32, end: u32) -> DocumentSymbol {
+        #[allow(deprecated)]
+        DocumentSymbol {
+            name: name.to_string(),
+            detail: None,
+            kind: SymbolKind::FUNCTION,
+            tags: None,
+            deprecated: None,
+            range: Range::new(Position::new(start, 0), Position::new(end, 1)),
+            selection_range: Range::new(Position::new(start, 3), Position::new(start, 6)),
+            children: None,
+        }
+    }
+
+    fn uri(path: &str) -> Url {
+        format!("bird:///0000/{}", path).parse().unwrap()
+    }
+
+    #[test]
+    fn innermost_prefers_nested_functions() {
+        let functions = [function("outer", 0, 10), function("inner", 2, 4)];
+        let name = |line| innermost(&functions, Position::new(line, 0)).map(|x| x.name.as_str());
+        assert_eq!(name(3), Some("inner"));
+        assert_eq!(name(5), Some("outer"));
+        assert_eq!(name(11), None);
+    }
+
+    #[test]
+    fn functions_are_flattened_from_any_depth() {
+        let mut class = function("class", 0, 20);
+        class.kind = SymbolKind::CLASS;
+        let mut method = function("method", 1, 10);
+        method.kind = SymbolKind::METHOD;
+        method.children = Some(vec![function("closure", 2, 3)]);
+        class.children = Some(vec![method]);
+
+        let mut result = Vec::new();
+        flatten_functions(vec![class, function("free", 21, 22)], &mut result);
+        let names = result.iter().map(|x| x.name.as_str()).collect::<Vec<_>>();
+        assert_eq!(names, ["closure", "method", "free"]);
+    }
+
+    #[test]
+    fn calls_are_grouped_by_item() {
+        let (a, b) = (uri("a.rs"), uri("b.rs"));
+        let call = |line| Range::new(Position::new(line, 0), Position::new(line, 1));
+        let mut calls = Calls::default();
+        calls.add(to_item(&function("f", 0, 5), &a), call(1));
+        calls.add(to_item(&function("g", 6, 9), &a), call(7));
+        calls.add(to_item(&function("f", 0, 5), &a), call(2));
+        calls.add(to_item(&function("f", 0, 5), &a), call(1));
+        calls.add(to_item(&function("f", 0, 5), &b), call(3));
+
+        let found = calls
+            .into_inner()
+            .map(|(item, ranges)| (item.name, item.uri, ranges))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            found,
+            [
+                ("f".to_string(), a.clone(), vec![call(1), call(2)]),
+                ("g".to_string(), a, vec![call(7)]),
+                ("f".to_string(), b, vec![call(3)]),
+            ]
+        );
+    }
+}
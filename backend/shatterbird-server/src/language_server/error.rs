@@ -29,13 +29,6 @@ pub enum LspError {
 }
 
 impl LspError {
-    pub fn not_found<T: ToString>(url: &Url, message: T) -> Self {
-        Self::FileNotFound {
-            url: url.clone(),
-            message: Some(message.to_string()),
-        }
-    }
-
     pub fn bad_request<T: Into<Report>>(err: T) -> Self {
         Self::BadRequest(err.into())
     }
@@ -55,8 +48,8 @@ impl From<FindError> for LspError {
     fn from(value: FindError) -> Self {
         match value {
             FindError::CantResolve(err) => err.into(),
-            FindError::NotATextFile => LspError::BadRequest(eyre!("not a text file")),
-            FindError::InvalidLineNumber => LspError::BadRequest(eyre!("invalid line number")),
+            FindError::NotATextFile => LspError::bad_request(eyre!("not a text file")),
+            FindError::InvalidLineNumber => LspError::bad_request(eyre!("invalid line number")),
             FindError::Internal(err) => err.into(),
         }
     }
@@ -0,0 +1,91 @@
+//! Requests whose parameters extend the ones from LSP
+
+use lsp_types::Url;
+use serde::{Deserialize, Serialize};
+
+/// `workspace/symbol` scoped to a commit and split into pages
+pub enum WorkspaceSymbol {}
+
+impl lsp_types::request::Request for WorkspaceSymbol {
+    type Params = WorkspaceSymbolParams;
+    type Result = Option<lsp_types::WorkspaceSymbolResponse>;
+    const METHOD: &'static str = "workspace/symbol";
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WorkspaceSymbolParams {
+    #[serde(flatten)]
+    pub base: lsp_types::WorkspaceSymbolParams,
+
+    /// Document or workspace folder, symbols of its commit are searched
+    pub uri: Option<Url>,
+
+    /// How many symbols to skip
+    #[serde(default)]
+    pub offset: usize,
+
+    /// Size of the page
+    pub limit: Option<usize>,
+}
+
+/// `textDocument/references` with the text around every reference, grouped by file
+pub enum ReferencesWithContext {}
+
+impl lsp_types::request::Request for ReferencesWithContext {
+    type Params = ReferencesWithContextParams;
+    type Result = ReferencesWithContextResult;
+    const METHOD: &'static str = "shatterbird/referencesWithContext";
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReferencesWithContextParams {
+    #[serde(flatten)]
+    pub base: lsp_types::ReferenceParams,
+
+    /// How many lines before and after each reference to include
+    #[serde(default)]
+    pub context_lines: u32,
+
+    /// How many references to skip
+    #[serde(default)]
+    pub offset: usize,
+
+    /// Size of the page
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReferencesWithContextResult {
+    /// Number of references in all pages
+    pub total: usize,
+
+    /// Files of the references on this page, in the order of their URIs
+    pub files: Vec<FileReferences>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FileReferences {
+    pub uri: Url,
+
+    /// Number of references to the file in all pages
+    pub count: usize,
+
+    pub references: Vec<ReferenceWithContext>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReferenceWithContext {
+    pub range: lsp_types::Range,
+
+    /// Number of the first line in `lines`
+    pub first_line: u32,
+
+    /// Lines of the reference together with the context lines around it,
+    /// empty when the file is missing or is not a text file
+    pub lines: Vec<String>,
+}
@@ -1,32 +1,53 @@
+use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
 
 use eyre::eyre;
 use futures::future::try_join_all;
-use lsp_types::{Hover, HoverContents, Location, MarkupContent, Position, Range};
+use lsp_types::lsif::{ItemKind, RangeTag};
+use lsp_types::{
+    DocumentHighlight, DocumentHighlightKind, DocumentSymbol, FoldingRange, Hover, HoverContents,
+    Location, MarkedString, MarkupContent, Moniker, MonikerKind, Position, Range,
+    SymbolInformation, TextDocumentPositionParams, Url,
+};
+use mongodb::bson::doc;
 use tracing::instrument;
 
 use shatterbird_storage::model::lang::{
-    EdgeInfoDiscriminants, VertexInfo, VertexInfoDiscriminants,
+    DocumentSymbolOrRangeBasedVec, EdgeInfoDiscriminants, RangeBasedDocumentSymbol, VertexInfo,
+    VertexInfoDiscriminants,
+};
+use shatterbird_storage::model::{
+    self, Commit, FileContent, Line, NavigationTarget, SymbolMoniker, Vertex,
 };
 use shatterbird_storage::util;
+use shatterbird_storage::Id;
 
+use crate::language_server::approximate;
 use crate::language_server::error::LspError;
+use crate::language_server::ext;
+use crate::settings;
 use crate::state::ServerState;
 
+/// Symbols returned by `workspace/symbol` when the client does not ask for a page size
+const WORKSPACE_SYMBOLS_PAGE: usize = 100;
+const WORKSPACE_SYMBOLS_MAX_PAGE: usize = 1000;
+
 #[instrument(skip(state), err)]
 pub async fn hover(
     state: Arc<ServerState>,
     req: lsp_types::HoverParams,
 ) -> Result<Option<Hover>, LspError> {
-    let found = util::graph::find(
-        &state.storage,
+    let found = approximate::find(
+        &state,
         Some(EdgeInfoDiscriminants::Hover),
         &req.text_document_position_params,
     )
     .await?;
-    let result = match found.found.into_iter().next() {
-        None => return Ok(None),
-        Some(x) => match x.data {
+    let precomputed = found.navigation.into_iter().find_map(|x| x.hover);
+    let mut result = match (precomputed, found.found.into_iter().next()) {
+        (Some(x), _) => x,
+        (None, None) => return Ok(None),
+        (None, Some(x)) => match x.data {
             VertexInfo::HoverResult { result } => result,
             _ => {
                 return Err(LspError::Internal(eyre!(
@@ -36,6 +57,25 @@ pub async fn hover(
             }
         },
     };
+    if let Some(ancestor) = &found.ancestor {
+        let note = format!(
+            "Approximate: this commit is not indexed, information is taken from {}",
+            ancestor.oid
+        );
+        result.contents = match result.contents {
+            HoverContents::Scalar(x) => HoverContents::Array(vec![x, MarkedString::String(note)]),
+            HoverContents::Array(mut x) => {
+                x.push(MarkedString::String(note));
+                HoverContents::Array(x)
+            }
+            HoverContents::Markup(mut x) => {
+                x.value.push_str(&format!("\n\n---\n\n{}", note));
+                HoverContents::Markup(x)
+            }
+        };
+        // The range is relative to the ancestor
+        result.range = None;
+    }
     Ok(Some(result))
 }
 
@@ -44,7 +84,7 @@ pub async fn hover_range(
     state: Arc<ServerState>,
     req: lsp_types::HoverParams,
 ) -> Result<Option<Hover>, LspError> {
-    let found = util::graph::find(&state.storage, None, &req.text_document_position_params).await?;
+    let found = approximate::find(&state, None, &req.text_document_position_params).await?;
     let text = format!(
         "```\nranges: {:?}\nline: {:?}\n```\n",
         found.ranges, found.line
@@ -53,8 +93,8 @@ pub async fn hover_range(
         Some(&x) => state.storage.get(x).await?,
         None => None,
     };
-    let line_no = match &range {
-        Some(x) => Some(util::graph::find_line_no(&state.storage, x).await?),
+    let line_nos = match &range {
+        Some(x) => Some(util::graph::find_line_nos(&state.storage, x).await?),
         None => None,
     };
     Ok(Some(Hover {
@@ -62,9 +102,9 @@ pub async fn hover_range(
             kind: lsp_types::MarkupKind::Markdown,
             value: text,
         }),
-        range: range.zip(line_no).map(|(range, line_no)| Range {
-            start: Position::new(line_no as _, range.start),
-            end: Position::new(line_no as _, range.end),
+        range: range.zip(line_nos).map(|(range, (start, end))| Range {
+            start: Position::new(start, range.start),
+            end: Position::new(end, range.end),
         }),
     }))
 }
@@ -74,21 +114,85 @@ pub async fn go_to_definition(
     state: Arc<ServerState>,
     req: lsp_types::GotoDefinitionParams,
 ) -> Result<Option<lsp_types::GotoDefinitionResponse>, LspError> {
-    let found = util::graph::find(
-        &state.storage,
-        Some(EdgeInfoDiscriminants::Definition),
+    let mut locations = find_locations(
+        &state,
         &req.text_document_position_params,
+        EdgeInfoDiscriminants::Definition,
+        VertexInfoDiscriminants::DefinitionResult,
     )
     .await?;
-    let results =
-        util::graph::filter_vertices(found.found, VertexInfoDiscriminants::DefinitionResult);
-    let items = util::graph::find_items(&state.storage, results.map(|x| x.id)).await?;
-    let locations = try_join_all(
-        items
-            .iter()
-            .map(|x| util::graph::to_location(&state.storage, x)),
+    if locations.is_empty() {
+        let items = external_definitions(&state, &req.text_document_position_params).await?;
+        locations = to_locations(&state, &items, None).await?;
+    }
+    let locations = dedup_locations(locations);
+    if locations.is_empty() {
+        return Ok(None);
+    }
+    Ok(Some(lsp_types::GotoDefinitionResponse::Array(locations)))
+}
+
+/// Declarations are emitted only by some indexers, so definitions are returned when there are none
+#[instrument(skip(state), err)]
+pub async fn go_to_declaration(
+    state: Arc<ServerState>,
+    req: lsp_types::request::GotoDeclarationParams,
+) -> Result<Option<lsp_types::request::GotoDeclarationResponse>, LspError> {
+    let mut locations = find_locations(
+        &state,
+        &req.text_document_position_params,
+        EdgeInfoDiscriminants::Declaration,
+        VertexInfoDiscriminants::DeclarationResult,
+    )
+    .await?;
+    if locations.is_empty() {
+        locations = find_locations(
+            &state,
+            &req.text_document_position_params,
+            EdgeInfoDiscriminants::Definition,
+            VertexInfoDiscriminants::DefinitionResult,
+        )
+        .await?;
+    }
+    let locations = dedup_locations(locations);
+    if locations.is_empty() {
+        return Ok(None);
+    }
+    Ok(Some(lsp_types::GotoDefinitionResponse::Array(locations)))
+}
+
+#[instrument(skip(state), err)]
+pub async fn go_to_implementation(
+    state: Arc<ServerState>,
+    req: lsp_types::request::GotoImplementationParams,
+) -> Result<Option<lsp_types::request::GotoImplementationResponse>, LspError> {
+    let locations = find_locations(
+        &state,
+        &req.text_document_position_params,
+        EdgeInfoDiscriminants::Implementation,
+        VertexInfoDiscriminants::ImplementationResult,
     )
     .await?;
+    let locations = dedup_locations(locations);
+    if locations.is_empty() {
+        return Ok(None);
+    }
+    Ok(Some(lsp_types::GotoDefinitionResponse::Array(locations)))
+}
+
+#[instrument(skip(state), err)]
+pub async fn go_to_type_definition(
+    state: Arc<ServerState>,
+    req: lsp_types::request::GotoTypeDefinitionParams,
+) -> Result<Option<lsp_types::request::GotoTypeDefinitionResponse>, LspError> {
+    let locations = find_locations(
+        &state,
+        &req.text_document_position_params,
+        EdgeInfoDiscriminants::TypeDefinition,
+        VertexInfoDiscriminants::TypeDefinitionResult,
+    )
+    .await?;
+    let locations = dedup_locations(locations);
     if locations.is_empty() {
         return Ok(None);
     }
@@ -100,23 +204,517 @@ pub async fn references(
     state: Arc<ServerState>,
     req: lsp_types::ReferenceParams,
 ) -> Result<Option<Vec<Location>>, LspError> {
-    let found = util::graph::find(
-        &state.storage,
+    let locations = reference_locations(&state, &req).await?;
+    if locations.is_empty() {
+        return Ok(None);
+    }
+    Ok(Some(locations))
+}
+
+/// References from the uploads of the commit and, if enabled, from other repositories.
+/// Definitions and declarations are left out unless the request includes them.
+pub(super) async fn reference_locations(
+    state: &ServerState,
+    req: &lsp_types::ReferenceParams,
+) -> Result<Vec<Location>, LspError> {
+    let include_declaration = req.context.include_declaration;
+    let is_wanted = |kind: &Option<ItemKind>| {
+        include_declaration || !matches!(kind, Some(ItemKind::Definitions | ItemKind::Declarations))
+    };
+
+    let found = approximate::find(
+        state,
         Some(EdgeInfoDiscriminants::References),
         &req.text_document_position,
     )
     .await?;
+    let translate_to = found.ancestor.as_ref().map(|_| found.commit);
+    let targets = found
+        .navigation
+        .into_iter()
+        .flat_map(|x| x.references)
+        .filter(|x| is_wanted(&x.kind));
+    let mut locations = navigation_locations(state, targets, translate_to).await?;
+    let results =
+        util::graph::filter_vertices(found.found, VertexInfoDiscriminants::ReferenceResult);
+    let items = util::graph::find_tagged_items(&state.storage, results.map(|x| x.id), None)
+        .await?
+        .into_iter()
+        .filter(|(_, kind)| is_wanted(kind))
+        .map(|(range, _)| range)
+        .collect::<Vec<_>>();
+    locations.extend(to_locations(state, &items, translate_to).await?);
+
+    if settings::get()?.cross_repo_references {
+        let items = external_references(state, &req.text_document_position)
+            .await?
+            .into_iter()
+            .filter(|(_, kind)| is_wanted(kind))
+            .map(|(range, _)| range)
+            .collect::<Vec<_>>();
+        locations.extend(to_locations(state, &items, None).await?);
+    }
+    Ok(dedup_locations(locations))
+}
+
+/// Occurrences of the symbol in the same file, found through its references
+#[instrument(skip(state), err)]
+pub async fn document_highlight(
+    state: Arc<ServerState>,
+    req: lsp_types::DocumentHighlightParams,
+) -> Result<Option<Vec<DocumentHighlight>>, LspError> {
+    let found = approximate::find(
+        &state,
+        Some(EdgeInfoDiscriminants::References),
+        &req.text_document_position_params,
+    )
+    .await?;
+    let translate_to = found.ancestor.as_ref().map(|_| found.commit);
+
+    let mut kinds = found
+        .navigation
+        .into_iter()
+        .flat_map(|x| x.references)
+        .map(|x| (x.range, x.kind))
+        .collect::<HashMap<_, _>>();
+    let mut items = match kinds.is_empty() {
+        true => Vec::new(),
+        false => state
+            .storage
+            .find::<model::Range>(
+                doc! {
+                    "_id": { "$in": kinds.keys().collect::<Vec<_>>() },
+                    "path": found.indexed_node,
+                },
+                None,
+            )
+            .await?
+            .into_iter()
+            .map(|x| {
+                let kind = kinds.remove(&x.id).flatten();
+                (x, kind)
+            })
+            .collect(),
+    };
     let results =
         util::graph::filter_vertices(found.found, VertexInfoDiscriminants::ReferenceResult);
+    items.extend(
+        util::graph::find_tagged_items(
+            &state.storage,
+            results.map(|x| x.id),
+            Some(found.indexed_node),
+        )
+        .await?,
+    );
+
+    let (ranges, kinds): (Vec<_>, Vec<_>) = items.into_iter().unzip();
+    let locations = to_locations(&state, &ranges, translate_to).await?;
+    let mut seen = HashSet::new();
+    let highlights = locations
+        .into_iter()
+        .zip(kinds)
+        .filter(|(location, _)| seen.insert(location.range))
+        .map(|(location, kind)| DocumentHighlight {
+            range: location.range,
+            kind: Some(match kind {
+                Some(ItemKind::Definitions | ItemKind::Declarations) => {
+                    DocumentHighlightKind::WRITE
+                }
+                Some(ItemKind::References) => DocumentHighlightKind::READ,
+                _ => DocumentHighlightKind::TEXT,
+            }),
+        })
+        .collect::<Vec<_>>();
+    if highlights.is_empty() {
+        return Ok(None);
+    }
+    Ok(Some(highlights))
+}
+
+/// Items of results reachable from the position by `edge`, precomputed or found in the graph
+pub(super) async fn find_locations(
+    state: &ServerState,
+    position: &TextDocumentPositionParams,
+    edge: EdgeInfoDiscriminants,
+    kind: VertexInfoDiscriminants,
+) -> Result<Vec<Location>, LspError> {
+    let found = approximate::find(state, Some(edge), position).await?;
+    let translate_to = found.ancestor.as_ref().map(|_| found.commit);
+    let targets = found.navigation.into_iter().flat_map(|x| x.targets(edge));
+    let mut locations = navigation_locations(state, targets, translate_to).await?;
+    let results = util::graph::filter_vertices(found.found, kind);
     let items = util::graph::find_items(&state.storage, results.map(|x| x.id)).await?;
-    let locations = try_join_all(
-        items
-            .iter()
-            .map(|x| util::graph::to_location(&state.storage, x)),
+    locations.extend(to_locations(state, &items, translate_to).await?);
+    Ok(locations)
+}
+
+/// Folding ranges of the indexed document with the same contents as the requested file
+#[instrument(skip(state), err)]
+pub async fn folding_range(
+    state: Arc<ServerState>,
+    req: lsp_types::FoldingRangeParams,
+) -> Result<Option<Vec<FoldingRange>>, LspError> {
+    let (commit, node) =
+        util::graph::resolve_with_commit(&state.storage, &req.text_document.uri).await?;
+    for document in util::graph::find_documents(&state.storage, commit.id, node.id).await? {
+        let found = util::graph::follow(
+            &state.storage,
+            document.id,
+            EdgeInfoDiscriminants::FoldingRange.into(),
+        )
+        .await?;
+        let result = found.into_iter().find_map(|x| match x.data {
+            VertexInfo::FoldingRangeResult { result } => Some(result),
+            _ => None,
+        });
+        if let Some(result) = result {
+            return Ok(Some(result));
+        }
+    }
+    Ok(None)
+}
+
+/// Outline of the indexed document with the same contents as the requested file
+#[instrument(skip(state), err)]
+pub async fn document_symbol(
+    state: Arc<ServerState>,
+    req: lsp_types::DocumentSymbolParams,
+) -> Result<Option<lsp_types::DocumentSymbolResponse>, LspError> {
+    let symbols = document_outline(&state, &req.text_document.uri).await?;
+    Ok(symbols.map(lsp_types::DocumentSymbolResponse::Nested))
+}
+
+/// Symbols of the first indexed document with the same contents as the file
+pub(super) async fn document_outline(
+    state: &ServerState,
+    uri: &Url,
+) -> Result<Option<Vec<DocumentSymbol>>, LspError> {
+    let (commit, node) = util::graph::resolve_with_commit(&state.storage, uri).await?;
+    let lines = match &node.content {
+        FileContent::Text { lines, .. } => lines,
+        _ => return Err(LspError::bad_request(eyre!("not a text file"))),
+    };
+    for document in util::graph::find_documents(&state.storage, commit.id, node.id).await? {
+        let found = util::graph::follow(
+            &state.storage,
+            document.id,
+            EdgeInfoDiscriminants::DocumentSymbol.into(),
+        )
+        .await?;
+        let result = found.into_iter().find_map(|x| match x.data {
+            VertexInfo::DocumentSymbolResult { result } => Some(result),
+            _ => None,
+        });
+        let symbols = match result {
+            Some(DocumentSymbolOrRangeBasedVec::DocumentSymbol(x)) => x,
+            Some(DocumentSymbolOrRangeBasedVec::RangeBased(x)) => {
+                resolve_symbols(state, lines, x).await?
+            }
+            None => continue,
+        };
+        return Ok(Some(symbols));
+    }
+    Ok(None)
+}
+
+/// Builds symbols from tags of the ranges they refer to.
+/// Ranges without a definition or declaration tag are replaced with their children.
+async fn resolve_symbols(
+    state: &ServerState,
+    lines: &[Id<Line>],
+    symbols: Vec<RangeBasedDocumentSymbol>,
+) -> eyre::Result<Vec<DocumentSymbol>> {
+    fn collect(symbols: &[RangeBasedDocumentSymbol], ids: &mut Vec<Id<model::Range>>) {
+        for symbol in symbols {
+            ids.push(symbol.range);
+            collect(&symbol.children, ids);
+        }
+    }
+    let mut ids = Vec::new();
+    collect(&symbols, &mut ids);
+
+    let ranges = state
+        .storage
+        .find::<model::Range>(doc! { "_id": { "$in": &ids } }, None)
+        .await?
+        .into_iter()
+        .map(|x| (x.id, x))
+        .collect::<HashMap<_, _>>();
+    let tags = state
+        .storage
+        .find::<Vertex>(
+            doc! {
+                "data.vertex": <&str>::from(VertexInfoDiscriminants::Range),
+                "data.range": { "$in": &ids },
+            },
+            None,
+        )
+        .await?
+        .into_iter()
+        .filter_map(|x| match x.data {
+            VertexInfo::Range {
+                range,
+                tag: Some(tag),
+            } => Some((range, tag)),
+            _ => None,
+        })
+        .collect::<HashMap<_, _>>();
+
+    fn build(
+        symbols: Vec<RangeBasedDocumentSymbol>,
+        lines: &[Id<Line>],
+        ranges: &HashMap<Id<model::Range>, model::Range>,
+        tags: &HashMap<Id<model::Range>, RangeTag>,
+    ) -> Vec<DocumentSymbol> {
+        let mut result = Vec::new();
+        for symbol in symbols {
+            let children = build(symbol.children, lines, ranges, tags);
+            let (text, kind, deprecated, full_range, detail) = match tags.get(&symbol.range) {
+                Some(RangeTag::Definition(x)) => {
+                    (&x.text, x.kind, x.deprecated, x.full_range, &x.detail)
+                }
+                Some(RangeTag::Declaration(x)) => {
+                    (&x.text, x.kind, x.deprecated, x.full_range, &x.detail)
+                }
+                _ => {
+                    result.extend(children);
+                    continue;
+                }
+            };
+            let selection_range = match ranges
+                .get(&symbol.range)
+                .and_then(|x| Some((x, util::graph::line_span(lines, x)?)))
+            {
+                Some((range, (start, end))) => Range {
+                    start: Position::new(start as u32, range.start),
+                    end: Position::new(end as u32, range.end),
+                },
+                None => {
+                    result.extend(children);
+                    continue;
+                }
+            };
+            #[allow(deprecated)]
+            result.push(DocumentSymbol {
+                name: text.clone(),
+                detail: detail.clone(),
+                kind,
+                tags: None,
+                deprecated: deprecated.then_some(true),
+                range: full_range,
+                selection_range,
+                children: Some(children).filter(|x| !x.is_empty()),
+            });
+        }
+        result
+    }
+    Ok(build(symbols, lines, &ranges, &tags))
+}
+
+/// Monikers attached to the symbol, including the ones that are only meaningful inside the dump
+#[instrument(skip(state), err)]
+pub async fn moniker(
+    state: Arc<ServerState>,
+    req: lsp_types::MonikerParams,
+) -> Result<Option<Vec<Moniker>>, LspError> {
+    let found = approximate::find(
+        &state,
+        Some(EdgeInfoDiscriminants::Moniker),
+        &req.text_document_position_params,
     )
     .await?;
-    if locations.is_empty() {
+    let vertices =
+        util::monikers::chain(&state.storage, found.found.into_iter().map(|x| x.id)).await?;
+    let mut monikers = Vec::new();
+    for vertex in state
+        .storage
+        .find::<Vertex>(doc! { "_id": { "$in": vertices } }, None)
+        .await?
+    {
+        match vertex.data {
+            VertexInfo::Moniker(x) if !monikers.contains(&x) => monikers.push(x),
+            _ => {}
+        }
+    }
+    if monikers.is_empty() {
         return Ok(None);
     }
-    Ok(Some(locations))
+    Ok(Some(monikers))
+}
+
+/// Symbols of the commit the request's document or workspace folder belongs to
+#[instrument(skip(state), err)]
+pub async fn workspace_symbol(
+    state: Arc<ServerState>,
+    req: ext::WorkspaceSymbolParams,
+) -> Result<Option<lsp_types::WorkspaceSymbolResponse>, LspError> {
+    // Without a document or a folder there is no commit to search in, so all uploads are searched
+    let commit = match &req.uri {
+        Some(uri) => {
+            let (commit, _) = util::graph::resolve_with_commit(&state.storage, uri).await?;
+            Some(commit)
+        }
+        None => None,
+    };
+    let limit = req
+        .limit
+        .unwrap_or(WORKSPACE_SYMBOLS_PAGE)
+        .min(WORKSPACE_SYMBOLS_MAX_PAGE);
+    let found = util::symbols::search(
+        &state.storage,
+        commit.as_ref(),
+        &req.base.query,
+        req.offset,
+        limit,
+    )
+    .await?;
+    let locations = match (&found.ancestor, &commit) {
+        (Some(ancestor), Some(commit)) => {
+            approximate::mark(ancestor);
+            symbol_locations_in(&state, &found.symbols, commit).await?
+        }
+        _ => found
+            .symbols
+            .iter()
+            .map(|x| Some(x.location.clone()))
+            .collect(),
+    };
+    #[allow(deprecated)]
+    let symbols = found
+        .symbols
+        .into_iter()
+        .zip(locations)
+        .filter_map(|(x, location)| {
+            Some(SymbolInformation {
+                name: x.name,
+                kind: x.kind,
+                tags: None,
+                deprecated: None,
+                location: location?,
+                container_name: x.container,
+            })
+        })
+        .collect();
+    Ok(Some(lsp_types::WorkspaceSymbolResponse::Flat(symbols)))
+}
+
+/// Translates locations of symbols found in an indexed ancestor into the requested commit,
+/// `None` for the ones which can't be translated
+async fn symbol_locations_in(
+    state: &ServerState,
+    symbols: &[model::Symbol],
+    commit: &Commit,
+) -> eyre::Result<Vec<Option<Location>>> {
+    let ranges = state
+        .storage
+        .find::<model::Range>(
+            doc! { "_id": { "$in": symbols.iter().filter_map(|x| x.range).collect::<Vec<_>>() } },
+            None,
+        )
+        .await?
+        .into_iter()
+        .map(|x| (x.id, x))
+        .collect::<HashMap<_, _>>();
+    try_join_all(symbols.iter().map(|x| async {
+        match x.range.and_then(|x| ranges.get(&x)) {
+            Some(range) => util::graph::to_location_in(&state.storage, range, commit.id).await,
+            None => util::graph::translate_location(&state.storage, &x.location, commit).await,
+        }
+    }))
+    .await
+}
+
+/// Follows import monikers of the symbol to definitions in uploads which export it
+async fn external_definitions(
+    state: &ServerState,
+    position: &TextDocumentPositionParams,
+) -> Result<Vec<model::Range>, LspError> {
+    let monikers = monikers_at(state, position).await?;
+    let imports = monikers
+        .into_iter()
+        .filter(|x| x.kind == MonikerKind::Import)
+        .collect::<Vec<_>>();
+    let exports =
+        util::monikers::matching(&state.storage, &imports, Some(MonikerKind::Export)).await?;
+    let results = follow_all(state, &exports, EdgeInfoDiscriminants::Definition).await?;
+    let results = util::graph::filter_vertices(results, VertexInfoDiscriminants::DefinitionResult);
+    Ok(util::graph::find_items(&state.storage, results.map(|x| x.id)).await?)
+}
+
+/// Finds usages of the symbol in other uploads sharing its import or export monikers,
+/// together with the properties of items they are found through
+async fn external_references(
+    state: &ServerState,
+    position: &TextDocumentPositionParams,
+) -> Result<Vec<(model::Range, Option<ItemKind>)>, LspError> {
+    let monikers = monikers_at(state, position).await?;
+    let matching = util::monikers::matching(&state.storage, &monikers, None).await?;
+    let results = follow_all(state, &matching, EdgeInfoDiscriminants::References).await?;
+    let results = util::graph::filter_vertices(results, VertexInfoDiscriminants::ReferenceResult);
+    Ok(util::graph::find_tagged_items(&state.storage, results.map(|x| x.id), None).await?)
+}
+
+async fn monikers_at(
+    state: &ServerState,
+    position: &TextDocumentPositionParams,
+) -> Result<Vec<SymbolMoniker>, LspError> {
+    let found = approximate::find(state, Some(EdgeInfoDiscriminants::Moniker), position).await?;
+    Ok(util::monikers::resolve(&state.storage, found.found.into_iter().map(|x| x.id)).await?)
+}
+
+async fn follow_all(
+    state: &ServerState,
+    monikers: &[SymbolMoniker],
+    edge: EdgeInfoDiscriminants,
+) -> eyre::Result<Vec<Vertex>> {
+    let found = try_join_all(
+        monikers
+            .iter()
+            .map(|x| util::graph::follow(&state.storage, x.vertex, edge.into())),
+    )
+    .await?;
+    Ok(found.into_iter().flatten().collect())
+}
+
+/// Precomputed locations are relative to the upload's commit, so translating them needs the ranges
+async fn navigation_locations(
+    state: &ServerState,
+    targets: impl Iterator<Item = NavigationTarget>,
+    translate_to: Option<Id<Commit>>,
+) -> eyre::Result<Vec<Location>> {
+    if translate_to.is_none() {
+        return Ok(targets.map(|x| x.location).collect());
+    }
+    let ranges = targets.map(|x| x.range).collect::<Vec<_>>();
+    let ranges = state
+        .storage
+        .find::<model::Range>(doc! { "_id": { "$in": ranges } }, None)
+        .await?;
+    to_locations(state, &ranges, translate_to).await
+}
+
+/// Results found through an indexed ancestor are translated back into the requested commit,
+/// those which can't be translated are dropped
+pub(super) async fn to_locations(
+    state: &ServerState,
+    items: &[model::Range],
+    translate_to: Option<Id<Commit>>,
+) -> eyre::Result<Vec<Location>> {
+    let locations = try_join_all(items.iter().map(|x| async move {
+        match translate_to {
+            Some(commit) => util::graph::to_location_in(&state.storage, x, commit).await,
+            None => util::graph::to_location(&state.storage, x).await.map(Some),
+        }
+    }))
+    .await?;
+    Ok(locations.into_iter().flatten().collect())
+}
+
+/// Results from several uploads of the same commit may point to the same place
+pub(super) fn dedup_locations(locations: Vec<Location>) -> Vec<Location> {
+    let mut seen = HashSet::new();
+    locations
+        .into_iter()
+        .filter(|x| seen.insert(x.clone()))
+        .collect()
 }
@@ -1,19 +1,29 @@
 use std::future::Future;
 use std::sync::Arc;
 
+use axum::http::Uri;
 use axum::routing::MethodRouter;
 use axum::{Json, Router};
 use futures::FutureExt;
 use lsp_types::{
-    lsp_request, HoverProviderCapability, InitializeResult, OneOf, ServerCapabilities, ServerInfo,
+    lsp_request, CallHierarchyServerCapability, DeclarationCapability,
+    FoldingRangeProviderCapability, HoverProviderCapability, ImplementationProviderCapability,
+    InitializeResult, OneOf, PositionEncodingKind, ServerCapabilities, ServerInfo,
+    TypeDefinitionProviderCapability,
 };
+use serde_json::json;
+use shatterbird_storage::util;
 use tracing::instrument;
 
 use crate::language_server::error::LspError;
 use crate::state::{AppState, ServerState};
 
+mod approximate;
+mod call_hierarchy;
 mod error;
+mod ext;
 mod methods;
+mod references;
 
 macro_rules! route {
     ($router:expr, $($method:tt -> $handler:expr),* $(,)?) => {
@@ -32,8 +42,31 @@ pub fn router() -> Router<Arc<ServerState>> {
         "initialize" -> initialize,
         "textDocument/hover" -> methods::hover,
         "textDocument/definition" -> methods::go_to_definition,
+        "textDocument/declaration" -> methods::go_to_declaration,
+        "textDocument/implementation" -> methods::go_to_implementation,
+        "textDocument/typeDefinition" -> methods::go_to_type_definition,
         "textDocument/references" -> methods::references,
+        "textDocument/documentHighlight" -> methods::document_highlight,
+        "textDocument/foldingRange" -> methods::folding_range,
+        "textDocument/documentSymbol" -> methods::document_symbol,
+        "textDocument/moniker" -> methods::moniker,
+        "textDocument/prepareCallHierarchy" -> call_hierarchy::prepare,
+        "callHierarchy/incomingCalls" -> call_hierarchy::incoming_calls,
+        "callHierarchy/outgoingCalls" -> call_hierarchy::outgoing_calls,
     )
+    .route(
+        "/workspace/symbol",
+        handler_for::<ext::WorkspaceSymbol, _, _>(methods::workspace_symbol),
+    )
+    .route(
+        "/shatterbird/referencesWithContext",
+        handler_for::<ext::ReferencesWithContext, _, _>(references::with_context),
+    )
+    .fallback(method_not_found)
+}
+
+async fn method_not_found(uri: Uri) -> LspError {
+    LspError::MethodNotFound(uri.path().trim_start_matches('/').to_string())
 }
 
 fn handler_for<R, F, Fut>(f: F) -> MethodRouter<Arc<ServerState>>
@@ -43,21 +76,50 @@ where
     Fut: Send + Future<Output = Result<R::Result, LspError>>,
 {
     let handler = move |state: AppState, params: Json<R::Params>| {
-        f(state.0, params.0).map(|res| res.map(Json))
+        approximate::scope(f(state.0, params.0).map(|res| res.map(Json)))
     };
     axum::routing::post(handler)
 }
 
-#[instrument(skip(_state), err)]
+#[instrument(skip(state), err)]
 async fn initialize(
-    _state: Arc<ServerState>,
+    state: Arc<ServerState>,
     req: lsp_types::InitializeParams,
 ) -> Result<InitializeResult, LspError> {
+    // Languages are reported for the commit the client works with, when it tells us one
+    #[allow(deprecated)]
+    let root = req
+        .workspace_folders
+        .as_ref()
+        .and_then(|x| x.first())
+        .map(|x| &x.uri)
+        .or(req.root_uri.as_ref());
+    let commit = match root {
+        Some(uri) => Some(
+            util::graph::resolve_with_commit(&state.storage, uri)
+                .await?
+                .0,
+        ),
+        None => None,
+    };
+    let languages = util::uploads::languages(&state.storage, commit.as_ref()).await?;
     Ok(InitializeResult {
         capabilities: ServerCapabilities {
+            position_encoding: Some(PositionEncodingKind::UTF16),
             hover_provider: Some(HoverProviderCapability::Simple(true)),
             definition_provider: Some(OneOf::Left(true)),
+            // Declarations fall back to definitions, so they are always available
+            declaration_provider: Some(DeclarationCapability::Simple(true)),
+            implementation_provider: Some(ImplementationProviderCapability::Simple(true)),
+            type_definition_provider: Some(TypeDefinitionProviderCapability::Simple(true)),
             references_provider: Some(OneOf::Left(true)),
+            document_highlight_provider: Some(OneOf::Left(true)),
+            folding_range_provider: Some(FoldingRangeProviderCapability::Simple(true)),
+            document_symbol_provider: Some(OneOf::Left(true)),
+            workspace_symbol_provider: Some(OneOf::Left(true)),
+            call_hierarchy_provider: Some(CallHierarchyServerCapability::Simple(true)),
+            moniker_provider: Some(OneOf::Left(true)),
+            experimental: Some(json!({ "languages": languages })),
             ..ServerCapabilities::default()
         },
         server_info: Some(ServerInfo {
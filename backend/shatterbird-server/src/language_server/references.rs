@@ -0,0 +1,118 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use lsp_types::Url;
+use mongodb::bson::doc;
+use tracing::{instrument, warn};
+
+use shatterbird_storage::model::{FileContent, Line};
+use shatterbird_storage::util;
+use shatterbird_storage::util::graph::ResolveError;
+
+use crate::language_server::error::LspError;
+use crate::language_server::ext::{
+    FileReferences, ReferenceWithContext, ReferencesWithContextParams, ReferencesWithContextResult,
+};
+use crate::language_server::methods::reference_locations;
+use crate::state::ServerState;
+
+/// References returned when the client does not ask for a page size
+const REFERENCES_PAGE: usize = 100;
+const REFERENCES_MAX_PAGE: usize = 1000;
+const MAX_CONTEXT_LINES: u32 = 10;
+
+/// References with the lines they are found at, so clients can show previews without fetching files.
+///
+/// Nothing is cached between pages: every request looks up the full set of references
+/// and cuts the page out of it, so `total` and the order stay consistent between pages.
+#[instrument(skip(state), err)]
+pub async fn with_context(
+    state: Arc<ServerState>,
+    req: ReferencesWithContextParams,
+) -> Result<ReferencesWithContextResult, LspError> {
+    let mut locations = reference_locations(&state, &req.base).await?;
+    locations.sort_by(|a, b| {
+        (a.uri.as_str(), a.range.start, a.range.end).cmp(&(
+            b.uri.as_str(),
+            b.range.start,
+            b.range.end,
+        ))
+    });
+    let mut counts = HashMap::new();
+    for location in &locations {
+        *counts.entry(location.uri.clone()).or_insert(0) += 1;
+    }
+
+    let total = locations.len();
+    let limit = req
+        .limit
+        .unwrap_or(REFERENCES_PAGE)
+        .min(REFERENCES_MAX_PAGE);
+    let context = req.context_lines.min(MAX_CONTEXT_LINES);
+    let mut files = Vec::<FileReferences>::new();
+    for location in locations.into_iter().skip(req.offset).take(limit) {
+        // Lines are filled in below, once per file
+        let reference = ReferenceWithContext {
+            range: location.range,
+            first_line: location.range.start.line.saturating_sub(context),
+            lines: Vec::new(),
+        };
+        match files.last_mut() {
+            Some(file) if file.uri == location.uri => file.references.push(reference),
+            _ => files.push(FileReferences {
+                count: counts[&location.uri],
+                uri: location.uri,
+                references: vec![reference],
+            }),
+        }
+    }
+    for file in &mut files {
+        fill_lines(&state, &file.uri, &mut file.references, context).await?;
+    }
+    Ok(ReferencesWithContextResult { total, files })
+}
+
+/// Loads the lines of each reference of the file together with `context` lines around it.
+/// References to files which are missing or not text are left without lines.
+async fn fill_lines(
+    state: &ServerState,
+    uri: &Url,
+    references: &mut [ReferenceWithContext],
+    context: u32,
+) -> Result<(), LspError> {
+    let node = match util::graph::resolve_url(&state.storage, uri).await {
+        Ok(x) => x,
+        Err(ResolveError::FileNotFound { .. }) => {
+            warn!("file {} of a reference is not found", uri);
+            return Ok(());
+        }
+        Err(e) => return Err(e.into()),
+    };
+    let lines = match &node.content {
+        FileContent::Text { lines, .. } => lines,
+        _ => return Ok(()),
+    };
+    let span = |x: &ReferenceWithContext| {
+        let start = x.first_line as usize;
+        let end = (x.range.end.line + context + 1) as usize;
+        start.min(lines.len())..end.min(lines.len())
+    };
+    let ids = references
+        .iter()
+        .flat_map(|x| lines[span(x)].iter().copied())
+        .collect::<Vec<_>>();
+    let texts = state
+        .storage
+        .find::<Line>(doc! { "_id": { "$in": ids } }, None)
+        .await?
+        .into_iter()
+        .map(|x| (x.id, x.text))
+        .collect::<HashMap<_, _>>();
+    for reference in references {
+        reference.lines = lines[span(reference)]
+            .iter()
+            .map(|x| texts.get(x).cloned().unwrap_or_default())
+            .collect();
+    }
+    Ok(())
+}
@@ -12,6 +12,7 @@ use tracing_subscriber::{prelude::*, registry::Registry};
 
 mod filesystem;
 mod language_server;
+mod permalink;
 mod settings;
 mod state;
 pub mod utils;
@@ -40,6 +41,7 @@ async fn main() -> eyre::Result<()> {
         .layer(tower_http::trace::TraceLayer::new_for_http())
         .nest("/api/fs", filesystem::router())
         .nest("/api/lsp", language_server::router())
+        .nest("/api/symbol", permalink::router())
         .fallback(|| async { (StatusCode::NOT_FOUND, "unknown route") })
         .with_state(state)
         .layer(tower_http::cors::CorsLayer::permissive());
@@ -0,0 +1,85 @@
+//! Stable links to symbols that do not depend on line numbers
+
+use std::str::FromStr;
+use std::sync::Arc;
+
+use axum::extract::{Path, Query, State};
+use axum::routing::get;
+use axum::{Json, Router};
+use futures::future::try_join_all;
+use serde::Deserialize;
+use tracing::warn;
+
+use shatterbird_storage::model::lang::{EdgeInfoDiscriminants, VertexInfoDiscriminants};
+use shatterbird_storage::model::Commit;
+use shatterbird_storage::util;
+
+use crate::state::AppState;
+use crate::utils::{AppResult, May404};
+use crate::ServerState;
+
+pub fn router() -> Router<Arc<ServerState>> {
+    Router::new().route("/:scheme/*identifier", get(by_moniker))
+}
+
+#[derive(Debug, Deserialize)]
+struct PackageFilter {
+    /// Package of the moniker, when the same identifier is exported by several packages
+    package: Option<String>,
+
+    /// Hash of a commit of the repository the link is resolved in, usually the head of its
+    /// main branch. The symbol is taken from the nearest indexed ancestor of the commit.
+    repository: Option<String>,
+}
+
+/// Definitions of the symbol in the most recent upload which exports it
+#[axum::debug_handler(state = Arc<ServerState>)]
+async fn by_moniker(
+    State(state): AppState,
+    Path((scheme, identifier)): Path<(String, String)>,
+    Query(filter): Query<PackageFilter>,
+) -> AppResult<May404<Json<Vec<lsp_types::Location>>>> {
+    let repository = match &filter.repository {
+        Some(oid) => {
+            let commit = match gix_hash::ObjectId::from_str(oid) {
+                Ok(x) => state.storage.get_by_oid::<Commit>(x).await?,
+                Err(e) => {
+                    warn!("invalid commit id {}: {}", oid, e);
+                    None
+                }
+            };
+            match commit {
+                Some(x) => Some(x),
+                None => return Ok(May404(None)),
+            }
+        }
+        None => None,
+    };
+    let moniker = util::monikers::latest(
+        &state.storage,
+        &scheme,
+        &identifier,
+        filter.package.as_deref(),
+        repository.as_ref(),
+    )
+    .await?;
+    let moniker = match moniker {
+        Some(x) => x,
+        None => return Ok(May404(None)),
+    };
+    let found = util::graph::follow(
+        &state.storage,
+        moniker.vertex,
+        EdgeInfoDiscriminants::Definition.into(),
+    )
+    .await?;
+    let results = util::graph::filter_vertices(found, VertexInfoDiscriminants::DefinitionResult);
+    let items = util::graph::find_items(&state.storage, results.map(|x| x.id)).await?;
+    let locations = try_join_all(
+        items
+            .iter()
+            .map(|x| util::graph::to_location(&state.storage, x)),
+    )
+    .await?;
+    Ok(May404(Some(locations).filter(|x| !x.is_empty()).map(Json)))
+}
@@ -24,4 +24,8 @@ pub struct Settings {
 
     #[arg(long, default_value = "127.0.0.1:3000")]
     pub addr: String,
+
+    /// Include usages from other indexed repositories in references, matched by monikers
+    #[arg(long)]
+    pub cross_repo_references: bool,
 }
@@ -8,6 +8,7 @@ use tracing::{info, instrument};
 
 pub use mongo_model::{Id, Model};
 
+pub mod migrations;
 pub mod model;
 pub mod serializers;
 pub mod ts;
@@ -0,0 +1,211 @@
+//! Миграции данных, сохранённых предыдущими версиями индексатора
+
+use mongodb::bson::doc;
+use mongodb::IndexModel;
+use tracing::{info, instrument, warn};
+
+use crate::model::{
+    Edge, Navigation, NavigationRange, Range, Symbol, SymbolMoniker, Upload, Vertex,
+};
+use crate::{util, Storage};
+
+/// Применяет все известные миграции. Каждая из них может безопасно запускаться повторно.
+#[instrument(skip_all, err)]
+pub async fn run(storage: &Storage) -> eyre::Result<()> {
+    create_indexes(storage).await?;
+    split_range_lines(storage).await?;
+    index_monikers(storage).await?;
+    report_unresolved_symbols(storage).await?;
+    fill_upload_languages(storage).await?;
+    fill_lowercase_symbol_names(storage).await?;
+    fill_reference_kinds(storage).await?;
+    Ok(())
+}
+
+/// Создаёт индексы, без которых запросы сервера просматривают коллекции целиком
+#[instrument(skip_all, err)]
+async fn create_indexes(storage: &Storage) -> eyre::Result<()> {
+    let index = |keys| IndexModel::builder().keys(keys).build();
+    // Its prefix also serves permalinks, which look up monikers without a version
+    storage
+        .access::<SymbolMoniker>()
+        .create_index(
+            index(doc! { "scheme": 1, "identifier": 1, "package": 1, "version": 1 }),
+            None,
+        )
+        .await?;
+    storage
+        .access::<NavigationRange>()
+        .create_index(index(doc! { "range": 1 }), None)
+        .await?;
+    storage
+        .access::<Range>()
+        .create_index(index(doc! { "path": 1, "upload": 1 }), None)
+        .await?;
+    storage
+        .access::<Range>()
+        .create_index(
+            index(doc! { "start_line": 1, "end_line": 1, "upload": 1 }),
+            None,
+        )
+        .await?;
+    storage
+        .access::<Vertex>()
+        .create_index(index(doc! { "data.range": 1 }), None)
+        .await?;
+    storage
+        .access::<Edge>()
+        .create_index(index(doc! { "data.in_vs": 1 }), None)
+        .await?;
+    storage
+        .access::<Symbol>()
+        .create_index(
+            index(doc! { "lowercase_name": 1, "commit": 1, "upload": 1 }),
+            None,
+        )
+        .await?;
+    info!("created indexes");
+    Ok(())
+}
+
+/// Заменяет поле `line_id` в [`Range`] на пару `start_line` и `end_line`.
+///
+/// Раньше многострочные подстроки хранили только первую строку и `end = u32::MAX`,
+/// поэтому после миграции они по-прежнему заканчиваются в конце первой строки.
+#[instrument(skip_all, err)]
+async fn split_range_lines(storage: &Storage) -> eyre::Result<()> {
+    let result = storage
+        .access::<Range>()
+        .update_many(
+            doc! { "line_id": { "$exists": true } },
+            vec![
+                doc! { "$set": { "start_line": "$line_id", "end_line": "$line_id" } },
+                doc! { "$unset": "line_id" },
+            ],
+            None,
+        )
+        .await?;
+    info!("migrated {} ranges", result.modified_count);
+    Ok(())
+}
+
+/// Строит индекс моникеров для загрузок, сохранённых до его появления
+#[instrument(skip_all, err)]
+async fn index_monikers(storage: &Storage) -> eyre::Result<()> {
+    let indexed = storage
+        .access::<SymbolMoniker>()
+        .distinct("upload", None, None)
+        .await?;
+    let uploads = storage
+        .find::<Upload>(doc! { "_id": { "$nin": indexed } }, None)
+        .await?;
+    for upload in &uploads {
+        util::monikers::index(storage, upload.id).await?;
+    }
+    info!("indexed monikers of {} uploads", uploads.len());
+    Ok(())
+}
+
+/// Сообщает о загрузках с символами документов, сохранёнными с идентификаторами исходного дампа.
+///
+/// Данные не удаляются: такие загрузки нужно импортировать заново
+/// или очистить их символы командой `uploads drop-unresolved-symbols`.
+#[instrument(skip_all, err)]
+async fn report_unresolved_symbols(storage: &Storage) -> eyre::Result<()> {
+    for upload in util::uploads::with_unresolved_symbols(storage).await? {
+        warn!("upload {} has unresolved document symbols", upload);
+    }
+    Ok(())
+}
+
+/// Заполняет [`Upload::languages`] из ключа проекта, который раньше состоял из языков через `+`
+#[instrument(skip_all, err)]
+async fn fill_upload_languages(storage: &Storage) -> eyre::Result<()> {
+    let result = storage
+        .access::<Upload>()
+        .update_many(
+            doc! { "languages": { "$exists": false } },
+            vec![doc! { "$set": { "languages": { "$split": ["$project", "+"] } } }],
+            None,
+        )
+        .await?;
+    info!("filled languages of {} uploads", result.modified_count);
+    Ok(())
+}
+
+/// Заполняет [`Symbol::lowercase_name`] у символов, сохранённых до его появления.
+///
+/// `$toLower` меняет регистр только ASCII, поэтому остальные имена переводятся по одному.
+#[instrument(skip_all, err)]
+async fn fill_lowercase_symbol_names(storage: &Storage) -> eyre::Result<()> {
+    let symbols = storage.access::<Symbol>();
+    let missing = doc! { "lowercase_name": { "$exists": false } };
+    let mut non_ascii = missing.clone();
+    non_ascii.insert("name", doc! { "$regex": "[^\\x00-\\x7F]" });
+    let mut count = 0;
+    for symbol in storage.find::<Symbol>(non_ascii, None).await? {
+        symbols
+            .update_one(
+                doc! { "_id": symbol.id },
+                doc! { "$set": { "lowercase_name": symbol.name.to_lowercase() } },
+                None,
+            )
+            .await?;
+        count += 1;
+    }
+    let result = symbols
+        .update_many(
+            missing,
+            vec![doc! { "$set": { "lowercase_name": { "$toLower": "$name" } } }],
+            None,
+        )
+        .await?;
+    info!(
+        "filled lowercase names of {} symbols",
+        count + result.modified_count
+    );
+    Ok(())
+}
+
+/// Заполняет [`NavigationTarget::kind`](crate::model::NavigationTarget::kind) у использований,
+/// сохранённых до его появления. Свойства рёбер `item` при этом уже недоступны, поэтому
+/// определениями считаются использования, которые есть среди определений той же записи.
+#[instrument(skip_all, err)]
+async fn fill_reference_kinds(storage: &Storage) -> eyre::Result<()> {
+    let result = storage
+        .access::<Navigation>()
+        .update_many(
+            doc! {
+                "references.0": { "$exists": true },
+                "references.kind": { "$exists": false },
+            },
+            vec![doc! {
+                "$set": {
+                    "references": {
+                        "$map": {
+                            "input": "$references",
+                            "as": "x",
+                            "in": {
+                                "$mergeObjects": ["$$x", {
+                                    "kind": {
+                                        "$cond": [
+                                            { "$in": ["$$x.range", "$definitions.range"] },
+                                            "definitions",
+                                            "references",
+                                        ]
+                                    }
+                                }]
+                            },
+                        }
+                    }
+                }
+            }],
+            None,
+        )
+        .await?;
+    info!(
+        "filled reference kinds of {} navigation records",
+        result.modified_count
+    );
+    Ok(())
+}
@@ -1,3 +1,4 @@
+use super::upload::Upload;
 use crate::ts;
 use mongo_model::{Id, Model};
 use serde::{Deserialize, Serialize};
@@ -29,20 +30,35 @@ pub struct Range {
     #[serde(rename = "_id")]
     pub id: Id<Self>,
 
-    /// Идентификатор строки, в которой находится подстрока
+    /// Идентификатор строки, в которой начинается подстрока
     #[ts(as = "ts::Id<Line>")]
-    pub line_id: Id<Line>,
+    pub start_line: Id<Line>,
+
+    /// Идентификатор строки, в которой заканчивается подстрока
+    #[ts(as = "ts::Id<Line>")]
+    pub end_line: Id<Line>,
 
     /// Полный путь к этому файлу
     // TODO: Move out of Range to reduce storage costs
     #[ts(as = "Vec<ts::Id<Line>>")]
     pub path: Vec<Id<Node>>,
 
-    /// Индекс первого символа подстроки
+    /// Индекс первого символа подстроки в строке `start_line`
     pub start: u32,
 
-    /// Индекс конца подстроки
+    /// Индекс конца подстроки в строке `end_line`
     pub end: u32,
+
+    /// Загрузка, которой принадлежит подстрока
+    #[ts(as = "Option<ts::Id<Upload>>")]
+    pub upload: Option<Id<Upload>>,
+}
+
+impl Range {
+    /// Проверяет, умещается ли подстрока в одну строку файла
+    pub fn is_single_line(&self) -> bool {
+        self.start_line == self.end_line
+    }
 }
 
 /// Содержимое файла, который не удалось разделить на отдельные строки
@@ -4,6 +4,7 @@ use strum::{EnumDiscriminants, EnumTryAs};
 use ts_rs::TS;
 
 use super::files::Range;
+use super::upload::Upload;
 use crate::{ts, Model};
 use mongo_model::Id;
 
@@ -20,6 +21,10 @@ pub struct Vertex {
     /// Информация об узле, предоставленная LSIF
     #[ts(inline, as = "ts::VertexInfo")]
     pub data: VertexInfo,
+
+    /// Загрузка, которой принадлежит узел
+    #[ts(as = "Option<ts::Id<Upload>>")]
+    pub upload: Option<Id<Upload>>,
 }
 
 /// Ребро графа, связывающее те или иные узлы
@@ -35,6 +40,10 @@ pub struct Edge {
     /// Информация об ребре, предоставленная LSIF
     #[ts(inline, as = "ts::EdgeInfo")]
     pub data: EdgeInfo,
+
+    /// Загрузка, которой принадлежит ребро
+    #[ts(as = "Option<ts::Id<Upload>>")]
+    pub upload: Option<Id<Upload>>,
 }
 
 // Same as https://docs.rs/lsp-types/latest/lsp_types/lsif/enum.Edge.html
@@ -60,6 +69,8 @@ pub enum EdgeInfo {
     DocumentLink(EdgeData),   // "textDocument/documentLink"
     DocumentSymbol(EdgeData), // "textDocument/documentSymbol"
     Diagnostic(EdgeData),     // "textDocument/diagnostic"
+
+    Attach(EdgeData),
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -110,7 +121,7 @@ pub enum VertexInfo {
         result: lsp_types::Hover,
     },
     DocumentSymbolResult {
-        result: lsp_types::lsif::DocumentSymbolOrRangeBasedVec,
+        result: DocumentSymbolOrRangeBasedVec,
     },
     DocumentLinkResult {
         result: Vec<DocumentLink>,
@@ -128,6 +139,20 @@ pub struct FoldingRange {
     pub collapsed_text: Option<String>,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum DocumentSymbolOrRangeBasedVec {
+    DocumentSymbol(Vec<lsp_types::DocumentSymbol>),
+    RangeBased(Vec<RangeBasedDocumentSymbol>),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RangeBasedDocumentSymbol {
+    pub range: Id<Range>,
+    #[serde(default)]
+    pub children: Vec<RangeBasedDocumentSymbol>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DocumentLink {
     pub range: Id<Range>,
@@ -174,7 +199,8 @@ impl EdgeInfo {
             | EdgeInfo::FoldingRange(x)
             | EdgeInfo::DocumentLink(x)
             | EdgeInfo::DocumentSymbol(x)
-            | EdgeInfo::Diagnostic(x) => Either::Left(std::iter::once(x.in_v)),
+            | EdgeInfo::Diagnostic(x)
+            | EdgeInfo::Attach(x) => Either::Left(std::iter::once(x.in_v)),
         }
     }
 
@@ -194,7 +220,8 @@ impl EdgeInfo {
             | EdgeInfo::FoldingRange(x)
             | EdgeInfo::DocumentLink(x)
             | EdgeInfo::DocumentSymbol(x)
-            | EdgeInfo::Diagnostic(x) => x.out_v,
+            | EdgeInfo::Diagnostic(x)
+            | EdgeInfo::Attach(x) => x.out_v,
         }
     }
 }
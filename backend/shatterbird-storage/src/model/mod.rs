@@ -1,5 +1,13 @@
 mod files;
 pub mod lang;
+mod moniker;
+mod navigation;
+mod symbol;
+mod upload;
 
 pub use files::{BlobFile, Commit, FileContent, Line, Node, Range};
 pub use lang::{Edge, Vertex};
+pub use moniker::SymbolMoniker;
+pub use navigation::{Navigation, NavigationRange, NavigationTarget};
+pub use symbol::Symbol;
+pub use upload::{ActiveUploads, Upload, UploadRoot, UploadState};
@@ -0,0 +1,46 @@
+use mongo_model::{Id, Model};
+use serde::{Deserialize, Serialize};
+use ts_rs::TS;
+
+use super::lang::Vertex;
+use super::upload::Upload;
+use crate::ts;
+
+/// Запись индекса моникеров: символ, который загрузка импортирует или экспортирует
+#[derive(Debug, Clone, Serialize, Deserialize, Model, TS)]
+#[mongo_model(collection = "monikers")]
+#[ts(export)]
+pub struct SymbolMoniker {
+    /// Идентификатор объекта в базе данных
+    #[ts(as = "ts::Id<Self>")]
+    #[serde(rename = "_id")]
+    pub id: Id<Self>,
+
+    /// Схема моникера, например `tsc` или `rust-analyzer`
+    pub scheme: String,
+
+    /// Идентификатор символа в пределах схемы
+    pub identifier: String,
+
+    /// Название пакета, если моникер связан с `packageInformation`
+    pub package: Option<String>,
+
+    /// Версия пакета
+    pub version: Option<String>,
+
+    /// Импортируется символ или экспортируется
+    #[ts(type = "\"import\" | \"export\"")]
+    pub kind: lsp_types::MonikerKind,
+
+    /// Узел с моникером
+    #[ts(as = "ts::Id<Vertex>")]
+    pub moniker: Id<Vertex>,
+
+    /// Диапазон или `resultSet`, к которому относится моникер
+    #[ts(as = "ts::Id<Vertex>")]
+    pub vertex: Id<Vertex>,
+
+    /// Загрузка, которой принадлежит моникер
+    #[ts(as = "ts::Id<Upload>")]
+    pub upload: Id<Upload>,
+}
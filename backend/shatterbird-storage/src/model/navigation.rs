@@ -0,0 +1,122 @@
+use mongo_model::{Id, Model};
+use serde::{Deserialize, Serialize};
+use ts_rs::TS;
+
+use super::files::Range;
+use super::lang::EdgeInfoDiscriminants;
+use super::upload::Upload;
+use crate::ts;
+
+/// Результаты запросов для набора результатов, вычисленные при импорте,
+/// чтобы не обходить граф при каждом запросе.
+///
+/// Подстроки одного символа обычно ведут в общий набор результатов, поэтому запись хранится
+/// один раз для набора, а подстроки ссылаются на неё через [`NavigationRange`]
+#[derive(Debug, Clone, Serialize, Deserialize, Model, TS)]
+#[mongo_model(collection = "navigation")]
+#[ts(export)]
+pub struct Navigation {
+    /// Идентификатор объекта в базе данных
+    #[ts(as = "ts::Id<Self>")]
+    #[serde(rename = "_id")]
+    pub id: Id<Self>,
+
+    /// Загрузка, которой принадлежит набор результатов
+    #[ts(as = "ts::Id<Upload>")]
+    pub upload: Id<Upload>,
+
+    /// Содержимое подсказки
+    #[ts(type = "unknown")]
+    pub hover: Option<lsp_types::Hover>,
+
+    /// Определения символа
+    pub definitions: Vec<NavigationTarget>,
+
+    /// Все использования символа, включая определения
+    pub references: Vec<NavigationTarget>,
+
+    /// Реализации символа
+    pub implementations: Vec<NavigationTarget>,
+}
+
+/// Связь подстроки с записью навигации, в которой лежат её результаты
+#[derive(Debug, Clone, Serialize, Deserialize, Model, TS)]
+#[mongo_model(collection = "navigation_ranges")]
+#[ts(export)]
+pub struct NavigationRange {
+    /// Идентификатор объекта в базе данных
+    #[ts(as = "ts::Id<Self>")]
+    #[serde(rename = "_id")]
+    pub id: Id<Self>,
+
+    /// Подстрока, для которой вычислены результаты
+    #[ts(as = "ts::Id<Range>")]
+    pub range: Id<Range>,
+
+    /// Запись с результатами
+    #[ts(as = "ts::Id<Navigation>")]
+    pub navigation: Id<Navigation>,
+
+    /// Загрузка, которой принадлежит подстрока
+    #[ts(as = "ts::Id<Upload>")]
+    pub upload: Id<Upload>,
+}
+
+/// Подстрока, на которую ведёт навигация, вместе с её расположением
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct NavigationTarget {
+    /// Подстрока
+    #[ts(as = "ts::Id<Range>")]
+    pub range: Id<Range>,
+
+    /// Расположение подстроки в коммите загрузки
+    #[ts(type = "unknown")]
+    pub location: lsp_types::Location,
+
+    /// Свойство ребра `item`, через которое найдена подстрока
+    #[serde(default)]
+    #[ts(type = "\"declarations\" | \"definitions\" | \"references\" | null")]
+    pub kind: Option<lsp_types::lsif::ItemKind>,
+}
+
+impl Navigation {
+    /// Вычисляются ли при импорте ответы на запросы, соответствующие ребру графа
+    pub fn covers(edge: EdgeInfoDiscriminants) -> bool {
+        matches!(
+            edge,
+            EdgeInfoDiscriminants::Hover
+                | EdgeInfoDiscriminants::Definition
+                | EdgeInfoDiscriminants::References
+                | EdgeInfoDiscriminants::Implementation
+        )
+    }
+
+    /// Подстроки, на которые ведёт запрос, соответствующий ребру графа
+    pub fn targets(self, edge: EdgeInfoDiscriminants) -> Vec<NavigationTarget> {
+        match edge {
+            EdgeInfoDiscriminants::Definition => self.definitions,
+            EdgeInfoDiscriminants::References => self.references,
+            EdgeInfoDiscriminants::Implementation => self.implementations,
+            _ => Vec::new(),
+        }
+    }
+
+    /// Есть ли в записи ответ на запрос, соответствующий ребру графа
+    pub fn answers(&self, edge: EdgeInfoDiscriminants) -> bool {
+        match edge {
+            EdgeInfoDiscriminants::Hover => self.hover.is_some(),
+            EdgeInfoDiscriminants::Definition => !self.definitions.is_empty(),
+            EdgeInfoDiscriminants::References => !self.references.is_empty(),
+            EdgeInfoDiscriminants::Implementation => !self.implementations.is_empty(),
+            _ => false,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.hover.is_none()
+            && self.definitions.is_empty()
+            && self.references.is_empty()
+            && self.implementations.is_empty()
+    }
+}
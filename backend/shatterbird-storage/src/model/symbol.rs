@@ -0,0 +1,48 @@
+use mongo_model::{Id, Model};
+use serde::{Deserialize, Serialize};
+use ts_rs::TS;
+
+use super::files::{Commit, Range};
+use super::upload::Upload;
+use crate::ts;
+
+/// Запись индекса символов коммита, по которому работает поиск символов в рабочей области
+#[derive(Debug, Clone, Serialize, Deserialize, Model, TS)]
+#[mongo_model(collection = "symbols")]
+#[ts(export)]
+pub struct Symbol {
+    /// Идентификатор объекта в базе данных
+    #[ts(as = "ts::Id<Self>")]
+    #[serde(rename = "_id")]
+    pub id: Id<Self>,
+
+    /// Имя символа
+    pub name: String,
+
+    /// Имя символа в нижнем регистре, по нему идёт поиск
+    #[serde(default)]
+    pub lowercase_name: String,
+
+    /// Вид символа
+    #[ts(type = "number")]
+    pub kind: lsp_types::SymbolKind,
+
+    /// Имя символа, внутри которого определён этот
+    pub container: Option<String>,
+
+    /// Расположение определения в коммите
+    #[ts(type = "unknown")]
+    pub location: lsp_types::Location,
+
+    /// Подстрока определения, если символ взят не из полного `DocumentSymbol`
+    #[ts(as = "Option<ts::Id<Range>>")]
+    pub range: Option<Id<Range>>,
+
+    /// Коммит, в котором определён символ
+    #[ts(as = "ts::Id<Commit>")]
+    pub commit: Id<Commit>,
+
+    /// Загрузка, которой принадлежит символ
+    #[ts(as = "ts::Id<Upload>")]
+    pub upload: Id<Upload>,
+}
@@ -0,0 +1,87 @@
+use mongo_model::{Id, Model};
+use mongodb::bson::DateTime;
+use serde::{Deserialize, Serialize};
+use ts_rs::TS;
+
+use super::files::Commit;
+use crate::ts;
+
+/// Результат импорта одного LSIF-дампа (или нескольких, объединённых в один)
+#[derive(Debug, Clone, Serialize, Deserialize, Model, TS)]
+#[mongo_model(collection = "uploads")]
+#[ts(export)]
+pub struct Upload {
+    /// Идентификатор объекта в базе данных
+    #[ts(as = "ts::Id<Self>")]
+    #[serde(rename = "_id")]
+    pub id: Id<Self>,
+
+    /// Коммиты, к которым относятся документы дампа
+    #[ts(as = "Vec<ts::Id<Commit>>")]
+    pub commits: Vec<Id<Commit>>,
+
+    /// Сопоставление путей в дампе с коммитами
+    pub roots: Vec<UploadRoot>,
+
+    /// Проект, для которого построен дамп: имя или адрес вершин `project`, а если их нет, то `kind`
+    pub project: String,
+
+    /// Языки проекта: значения `kind` вершин `project`
+    #[serde(default)]
+    pub languages: Vec<String>,
+
+    /// Информация об инструменте, построившем дамп
+    #[ts(as = "Option<ts::ToolInfo>")]
+    pub tool_info: Option<lsp_types::lsif::ToolInfo>,
+
+    /// Время начала импорта
+    #[ts(as = "ts::DateTime")]
+    pub created_at: DateTime,
+
+    /// Состояние импорта
+    pub state: UploadState,
+}
+
+/// Префикс путей документов дампа, соответствующий корню коммита
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct UploadRoot {
+    /// Префикс пути
+    pub prefix: String,
+
+    /// Коммит, в котором ищутся документы с этим префиксом
+    #[ts(as = "ts::Id<Commit>")]
+    pub commit: Id<Commit>,
+}
+
+/// Состояние импорта. При поиске используются только загрузки из [`ActiveUploads`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub enum UploadState {
+    /// Данные ещё сохраняются
+    Processing,
+
+    /// Данные сохранены, загрузка добавлена в [`ActiveUploads`]
+    Ready,
+
+    /// Загрузка заменена более новой, её данные удаляются
+    Superseded,
+}
+
+/// Единственный документ со списком загрузок, которые используются при поиске.
+///
+/// Замена загрузок меняет только этот документ, поэтому запросы видят либо старые загрузки, либо новую.
+#[derive(Debug, Clone, Serialize, Deserialize, Model)]
+#[mongo_model(collection = "active_uploads")]
+pub struct ActiveUploads {
+    /// Идентификатор объекта в базе данных
+    #[serde(rename = "_id")]
+    pub id: Id<Self>,
+
+    /// Загрузки, используемые при поиске
+    pub uploads: Vec<Id<Upload>>,
+
+    /// Номер изменения списка, по которому замена загрузок узнаёт о параллельных изменениях
+    #[serde(default)]
+    pub version: i64,
+}
@@ -37,3 +37,27 @@ pub struct VertexInfo {
     #[ts(inline)]
     pub vertex: VertexInfoDiscriminants,
 }
+
+/// Дата и время в формате MongoDB Extended JSON
+#[derive(TS)]
+#[ts(export)]
+pub struct DateTime {
+    #[ts(rename = "$date", type = "{ $numberLong: string }")]
+    pub date: String,
+}
+
+/// Информация об инструменте, построившем LSIF-дамп
+#[derive(TS)]
+#[ts(export)]
+pub struct ToolInfo {
+    /// Название инструмента
+    pub name: String,
+
+    /// Аргументы, с которыми был запущен инструмент
+    #[ts(optional)]
+    pub args: Option<Vec<String>>,
+
+    /// Версия инструмента
+    #[ts(optional)]
+    pub version: Option<String>,
+}
@@ -0,0 +1,98 @@
+//! Преобразование позиций между кодировками, которые используются в LSP и LSIF
+
+pub use lsp_types::lsif::Encoding;
+
+/// Кодировка, в которой в базе данных хранятся все позиции внутри строк
+pub const CANONICAL: Encoding = Encoding::Utf16;
+
+fn units(ch: char, encoding: Encoding) -> u32 {
+    match encoding {
+        Encoding::Utf8 => ch.len_utf8() as _,
+        Encoding::Utf16 => ch.len_utf16() as _,
+    }
+}
+
+/// Переводит смещение `offset` внутри строки `text` из кодировки `from` в кодировку `to`.
+///
+/// Смещения за концом строки сохраняют свой остаток, а `u32::MAX` всегда остаётся концом строки.
+/// Смещение внутри символа переводится в конец этого символа.
+pub fn convert(text: &str, offset: u32, from: Encoding, to: Encoding) -> u32 {
+    if from == to || offset == u32::MAX {
+        return offset;
+    }
+    let (mut seen, mut result) = (0u32, 0u32);
+    for ch in text.chars() {
+        if seen >= offset {
+            return result;
+        }
+        seen += units(ch, from);
+        result += units(ch, to);
+    }
+    result.saturating_add(offset.saturating_sub(seen))
+}
+
+/// Находит байтовый индекс в `text`, соответствующий смещению в кодировке [`CANONICAL`]
+pub fn byte_offset(text: &str, offset: u32) -> usize {
+    (convert(text, offset, CANONICAL, Encoding::Utf8) as usize).min(text.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ascii_is_unchanged() {
+        assert_eq!(convert("hello", 3, Encoding::Utf8, Encoding::Utf16), 3);
+        assert_eq!(convert("hello", 3, Encoding::Utf16, Encoding::Utf8), 3);
+    }
+
+    #[test]
+    fn multibyte_characters() {
+        // "д" takes two bytes in UTF-8 and one unit in UTF-16
+        let text = "дом x";
+        assert_eq!(convert(text, 6, Encoding::Utf8, Encoding::Utf16), 3);
+        assert_eq!(convert(text, 3, Encoding::Utf16, Encoding::Utf8), 6);
+        assert_eq!(convert(text, 4, Encoding::Utf16, Encoding::Utf8), 7);
+    }
+
+    #[test]
+    fn surrogate_pairs() {
+        // "🦀" takes four bytes in UTF-8 and two units in UTF-16
+        let text = "a🦀b";
+        assert_eq!(convert(text, 5, Encoding::Utf8, Encoding::Utf16), 3);
+        assert_eq!(convert(text, 3, Encoding::Utf16, Encoding::Utf8), 5);
+        assert_eq!(convert(text, 4, Encoding::Utf16, Encoding::Utf8), 6);
+    }
+
+    #[test]
+    fn offset_inside_character_moves_to_its_end() {
+        assert_eq!(convert("a🦀b", 2, Encoding::Utf8, Encoding::Utf16), 3);
+        assert_eq!(convert("a🦀b", 2, Encoding::Utf16, Encoding::Utf8), 5);
+        assert_eq!(convert("дом", 1, Encoding::Utf8, Encoding::Utf16), 1);
+    }
+
+    #[test]
+    fn offset_past_end_keeps_remainder() {
+        assert_eq!(convert("дом", 8, Encoding::Utf8, Encoding::Utf16), 5);
+        assert_eq!(convert("дом", 5, Encoding::Utf16, Encoding::Utf8), 8);
+    }
+
+    #[test]
+    fn end_of_line_is_preserved() {
+        let text = "дом 🦀";
+        assert_eq!(
+            convert(text, u32::MAX, Encoding::Utf8, Encoding::Utf16),
+            u32::MAX
+        );
+        assert_eq!(
+            convert(text, u32::MAX, Encoding::Utf16, Encoding::Utf8),
+            u32::MAX
+        );
+    }
+
+    #[test]
+    fn byte_offset_is_clamped() {
+        assert_eq!(byte_offset("a🦀b", 3), 5);
+        assert_eq!(byte_offset("a🦀b", u32::MAX), 6);
+    }
+}
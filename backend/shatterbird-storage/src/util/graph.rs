@@ -1,14 +1,19 @@
-use std::collections::HashMap;
+use std::cmp::Reverse;
+use std::collections::{HashMap, HashSet};
 
 use eyre::{eyre, OptionExt, Report};
-use futures::join;
+use futures::{join, TryStreamExt};
+use lsp_types::lsif::ItemKind;
 use lsp_types::{Position, Url};
 use mongodb::bson::doc;
+use serde::Deserialize;
 use thiserror::Error;
 use tracing::{instrument, trace};
 
 use crate::model::lang::{EdgeInfo, EdgeInfoDiscriminants, VertexInfo, VertexInfoDiscriminants};
-use crate::model::{Commit, Edge, FileContent, Line, Node, Range, Vertex};
+use crate::model::{
+    Commit, Edge, FileContent, Line, Navigation, NavigationRange, Node, Range, Upload, Vertex,
+};
 use crate::{util, Id, Storage};
 
 #[derive(Debug, Error)]
@@ -36,6 +41,14 @@ fn not_found<T: ToString>(url: &Url, message: T) -> ResolveError {
 
 #[instrument(skip_all, fields(uri = %uri))]
 pub async fn resolve_url(storage: &Storage, uri: &Url) -> Result<Node, ResolveError> {
+    Ok(resolve_with_commit(storage, uri).await?.1)
+}
+
+/// То же, что и [`resolve_url`], но также возвращает коммит, в котором находится файл
+pub async fn resolve_with_commit(
+    storage: &Storage,
+    uri: &Url,
+) -> Result<(Commit, Node), ResolveError> {
     let splitted = uri
         .path()
         .split('/')
@@ -69,19 +82,51 @@ pub async fn resolve_url(storage: &Storage, uri: &Url) -> Result<Node, ResolveEr
             }
         };
     }
-    storage
+    let node = storage
         .get(curr)
         .await?
-        .ok_or_else(|| ResolveError::Internal(eyre!("can't find {}", curr)))
+        .ok_or_else(|| ResolveError::Internal(eyre!("can't find {}", curr)))?;
+    Ok((commit, node))
+}
+
+/// Находит файл по пути относительно корня коммита, если он существует
+async fn resolve_path(
+    storage: &Storage,
+    commit: &Commit,
+    path: &[&str],
+) -> Result<Option<Node>, Report> {
+    let mut curr = commit.root;
+    for component in path {
+        let node = storage
+            .get(curr)
+            .await?
+            .ok_or_else(|| eyre!("can't find {}", curr))?;
+        curr = match node.content {
+            FileContent::Directory { children } => match children.get(*component) {
+                Some(x) => *x,
+                None => return Ok(None),
+            },
+            _ => return Ok(None),
+        };
+    }
+    storage.get(curr).await
 }
 
 #[derive(Debug)]
 pub struct ResolvedPosition {
+    pub commit: Id<Commit>,
     pub node: Id<Node>,
+    /// Файл, в котором искались подстроки: сам файл или его версия в проиндексированном предке
+    pub indexed_node: Id<Node>,
     pub line: Id<Line>,
     pub position: u32,
     pub ranges: Vec<Id<Range>>,
     pub found: Vec<Vertex>,
+    /// Предвычисленные результаты для загрузок, у которых они есть
+    pub navigation: Vec<Navigation>,
+    /// Проиндексированный предок, через который найдены результаты, если у самого коммита
+    /// нет загрузок. Такие результаты приблизительны.
+    pub ancestor: Option<Commit>,
 }
 
 #[derive(Debug, Error)]
@@ -103,19 +148,138 @@ pub enum FindError {
     ),
 }
 
+/// Сколько коммитов просматривается при поиске проиндексированного предка
+const ANCESTOR_SEARCH_LIMIT: usize = 100;
+
+/// Находит ближайшего предка с готовыми загрузками, если у самого коммита их нет
+#[instrument(skip_all, fields(commit = %commit.id), ret, err)]
+pub(crate) async fn find_indexed_ancestor(
+    storage: &Storage,
+    commit: &Commit,
+) -> eyre::Result<Option<(Commit, Vec<Id<Upload>>)>> {
+    let active = util::uploads::active(storage).await?;
+    let active = &active;
+    let ready = |commits: Vec<Id<Commit>>| async move {
+        storage
+            .find::<Upload>(
+                doc! {
+                    "commits": { "$in": commits },
+                    "_id": { "$in": active },
+                },
+                None,
+            )
+            .await
+    };
+    if !ready(vec![commit.id]).await?.is_empty() {
+        return Ok(None);
+    }
+
+    let mut visited = HashSet::from([commit.id]);
+    let mut level = commit.parents.clone();
+    while !level.is_empty() && visited.len() < ANCESTOR_SEARCH_LIMIT {
+        level.retain(|x| visited.insert(*x));
+        let uploads = ready(level.clone()).await?;
+        // Parents are ordered, so the first parent wins among commits of the same depth
+        if let Some(&found) = level
+            .iter()
+            .find(|x| uploads.iter().any(|u| u.commits.contains(x)))
+        {
+            let ancestor = storage
+                .get(found)
+                .await?
+                .ok_or_else(|| eyre!("can't find {}", found))?;
+            let uploads = uploads
+                .into_iter()
+                .filter(|x| x.commits.contains(&found))
+                .map(|x| x.id)
+                .collect();
+            return Ok(Some((ancestor, uploads)));
+        }
+        level = parents(storage, &level).await?;
+    }
+    Ok(None)
+}
+
+/// Находит среди `candidates` ближайшего к `commit` предка, включая сам коммит.
+/// Родители упорядочены, поэтому среди коммитов одной глубины побеждает первый родитель.
+#[instrument(skip_all, fields(commit = %commit.id), ret, err)]
+pub(crate) async fn nearest_ancestor(
+    storage: &Storage,
+    commit: &Commit,
+    candidates: &HashSet<Id<Commit>>,
+) -> eyre::Result<Option<Id<Commit>>> {
+    let mut visited = HashSet::new();
+    let mut level = vec![commit.id];
+    while !level.is_empty() && visited.len() < ANCESTOR_SEARCH_LIMIT {
+        level.retain(|x| visited.insert(*x));
+        if let Some(found) = level.iter().find(|x| candidates.contains(x)) {
+            return Ok(Some(*found));
+        }
+        level = parents(storage, &level).await?;
+    }
+    Ok(None)
+}
+
+/// Оставляет из `commits` только те, которые не являются предками других коммитов из этого же списка
+#[instrument(skip_all, ret, err)]
+pub(crate) async fn newest_commits(
+    storage: &Storage,
+    commits: &HashSet<Id<Commit>>,
+) -> eyre::Result<HashSet<Id<Commit>>> {
+    let mut newest = commits.clone();
+    // Каждый коммит просматривает свою историю, пары нужны, чтобы не обходить её повторно
+    let mut visited = commits.iter().map(|&x| (x, x)).collect::<HashSet<_>>();
+    let mut level = visited.iter().copied().collect::<Vec<_>>();
+    while !level.is_empty() && visited.len() < ANCESTOR_SEARCH_LIMIT * commits.len() {
+        let ids = level.iter().map(|(_, x)| *x).collect::<Vec<_>>();
+        let found = storage
+            .find::<Commit>(doc! { "_id": { "$in": ids } }, None)
+            .await?
+            .into_iter()
+            .map(|x| (x.id, x.parents))
+            .collect::<HashMap<_, _>>();
+        level = level
+            .iter()
+            .flat_map(|(origin, commit)| {
+                let parents = found.get(commit).into_iter().flatten();
+                parents.map(move |x| (*origin, *x))
+            })
+            .filter(|x| visited.insert(*x))
+            .collect();
+        for (_, commit) in &level {
+            newest.remove(commit);
+        }
+    }
+    Ok(newest)
+}
+
+/// Родители коммитов в порядке коммитов
+async fn parents(storage: &Storage, commits: &[Id<Commit>]) -> eyre::Result<Vec<Id<Commit>>> {
+    let found = storage
+        .find::<Commit>(doc! { "_id": { "$in": commits } }, None)
+        .await?;
+    Ok(commits
+        .iter()
+        .filter_map(|id| found.iter().find(|x| x.id == *id))
+        .flat_map(|x| x.parents.iter().copied())
+        .collect())
+}
+
 #[instrument(skip_all, fields(uri = %position.text_document.uri, edge=?edge, position = ?position.position), ret)]
 pub async fn find(
     storage: &Storage,
     edge: Option<EdgeInfoDiscriminants>,
     position: &lsp_types::TextDocumentPositionParams,
 ) -> Result<ResolvedPosition, FindError> {
-    let node = resolve_url(storage, &position.text_document.uri).await?;
+    let uri = &position.text_document.uri;
+    let (commit, node) = resolve_with_commit(storage, uri).await?;
     let lines = match &node.content {
         FileContent::Text { lines, .. } => lines,
         _ => return Err(FindError::NotATextFile),
     };
+    let line_no = position.position.line as usize;
     let line = lines
-        .get(position.position.line as usize)
+        .get(line_no)
         .copied()
         .ok_or_else(|| FindError::InvalidLineNumber)?;
     let line = storage
@@ -123,33 +287,133 @@ pub async fn find(
         .await?
         .ok_or_else(|| FindError::Internal(eyre!("can't find {}", line)))?;
     let position = position.position.character;
+    let mut result = ResolvedPosition {
+        commit: commit.id,
+        node: node.id,
+        indexed_node: node.id,
+        line: line.id,
+        position,
+        ranges: Vec::new(),
+        found: Vec::new(),
+        navigation: Vec::new(),
+        ancestor: None,
+    };
+
+    // Unchanged lines share ids between commits, so a position in a commit without uploads
+    // can be translated to the same line of the file in the nearest indexed ancestor
+    let (node, lines, line_no, uploads) = match find_indexed_ancestor(storage, &commit).await? {
+        None => {
+            // Other commits with the same line have their own uploads, which must not leak in
+            let active = util::uploads::active(storage).await?;
+            let uploads = storage
+                .find::<Upload>(
+                    doc! { "commits": commit.id, "_id": { "$in": active } },
+                    None,
+                )
+                .await?
+                .into_iter()
+                .map(|x| x.id)
+                .collect();
+            (node.id, lines.clone(), line_no, uploads)
+        }
+        Some((ancestor, uploads)) => {
+            let path = uri
+                .path()
+                .split('/')
+                .filter(|x| !x.is_empty())
+                .skip(1)
+                .collect::<Vec<_>>();
+            let found = resolve_path(storage, &ancestor, &path).await?;
+            let (node, indexed) = match found {
+                Some(Node {
+                    id,
+                    content: FileContent::Text { lines, .. },
+                    ..
+                }) => (id, lines),
+                _ => return Ok(result),
+            };
+            let line_no = match translate_line(lines, line_no, &indexed) {
+                Some(x) => x,
+                None => return Ok(result),
+            };
+            trace!("translated to line {} of {}", line_no, ancestor.oid);
+            result.ancestor = Some(ancestor);
+            result.indexed_node = node;
+            (node, indexed, line_no, uploads)
+        }
+    };
 
-    let mut ranges = storage
+    // Single-line ranges are found by the line itself, multi-line ones among ranges of the file
+    let candidates = storage
         .find::<Range>(
             doc! {
-                "line_id": { "$eq": line.id },
-                "start": { "$lte": position },
-                "end": { "$gt": position },
+                "upload": { "$in": &uploads },
+                "$or": [
+                    {
+                        "start_line": { "$eq": line.id },
+                        "end_line": { "$eq": line.id },
+                        "start": { "$lte": position },
+                        "end": { "$gt": position },
+                    },
+                    {
+                        "path": { "$eq": node },
+                        "$expr": { "$ne": ["$start_line", "$end_line"] },
+                    },
+                ]
             },
             None,
         )
         .await?;
 
-    ranges.sort_unstable_by_key(|r| r.end - r.start);
-    let mut result = ResolvedPosition {
-        node: node.id,
-        line: line.id,
-        position,
-        ranges: ranges.iter().map(|i| i.id).collect(),
-        found: Vec::new(),
-    };
+    let mut ranges = covering(candidates, &uploads, &lines, line_no, position);
+
+    // Several uploads may cover the same position, so order ranges by size first,
+    // then prefer newer uploads
+    let upload_ids = ranges
+        .iter()
+        .filter_map(|(_, r)| r.upload)
+        .collect::<HashSet<_>>();
+    let created = storage
+        .find::<Upload>(
+            doc! { "_id": { "$in": upload_ids.into_iter().collect::<Vec<_>>() } },
+            None,
+        )
+        .await?
+        .into_iter()
+        .map(|x| (x.id, x.created_at))
+        .collect::<HashMap<_, _>>();
+    ranges.sort_unstable_by_key(|(size, r)| {
+        let created = r.upload.and_then(|x| created.get(&x)).copied();
+        (*size, Reverse(created), r.id)
+    });
+    let ranges = ranges.into_iter().map(|(_, r)| r).collect::<Vec<_>>();
+    result.ranges = ranges.iter().map(|i| i.id).collect();
 
-    let edge: &'static str = match edge {
-        Some(x) => x.into(),
+    let edge = match edge {
+        Some(x) => x,
         None => return Ok(result),
     };
+    // Uploads imported with a navigation table are answered without walking the graph,
+    // unless the table has no answers for this kind of request at all
+    let mut navigation = match Navigation::covers(edge) {
+        true => find_navigation(storage, &result.ranges).await?,
+        false => HashMap::new(),
+    };
+
+    // Each upload contributes results of its innermost matching range
+    let mut resolved = HashSet::new();
     for range in ranges {
+        if resolved.contains(&range.upload) {
+            continue;
+        }
         trace!("trying range {:?}", range.id);
+        if let Some(found) = navigation.remove(&range.id) {
+            if found.answers(edge) {
+                result.navigation.push(found);
+                resolved.insert(range.upload);
+            }
+            continue;
+        }
 
         let initital = storage
             .find_one::<Vertex>(
@@ -161,65 +425,220 @@ pub async fn find(
             )
             .await?
             .ok_or_eyre(eyre!("no matching vertex found for {}", range.id))?;
-        let mut queue = Vec::new();
-        queue.push(initital.id);
-        while let Some(vertex) = queue.pop() {
-            trace!("visiting vertex {:?}", vertex);
-            let outgoing: Vec<Edge> = storage
-                .find(
-                    doc! {
-                        "data.edge": { "$eq": edge },
-                        "data.out_v": { "$eq": vertex }
-                    },
-                    None,
-                )
-                .await?;
-            let outgoing = outgoing
-                .iter()
-                .flat_map(|e| e.data.in_vs())
-                .collect::<Vec<_>>();
-            if !outgoing.is_empty() {
-                trace!("found matching edges: {:?}", outgoing);
-                result.found = storage
-                    .find(
-                        doc! {
-                            "_id": {
-                                "$in": outgoing
-                            }
-                        },
-                        None,
-                    )
-                    .await?;
-                return Ok(result);
-            }
+        let found = follow(storage, initital.id, edge.into()).await?;
+        if !found.is_empty() {
+            result.found.extend(found);
+            resolved.insert(range.upload);
+        }
+    }
 
-            let next = storage
-                .find::<Edge>(
+    Ok(result)
+}
+
+/// Находит записи навигации подстрок, у которых они есть.
+/// Подстроки одного набора результатов получают копии одной записи
+pub async fn find_navigation(
+    storage: &Storage,
+    ranges: &[Id<Range>],
+) -> eyre::Result<HashMap<Id<Range>, Navigation>> {
+    let mapping = storage
+        .find::<NavigationRange>(doc! { "range": { "$in": ranges } }, None)
+        .await?;
+    let ids = mapping.iter().map(|x| x.navigation).collect::<HashSet<_>>();
+    let records = storage
+        .find::<Navigation>(
+            doc! { "_id": { "$in": ids.into_iter().collect::<Vec<_>>() } },
+            None,
+        )
+        .await?
+        .into_iter()
+        .map(|x| (x.id, x))
+        .collect::<HashMap<_, _>>();
+    Ok(mapping
+        .into_iter()
+        .filter_map(|x| Some((x.range, records.get(&x.navigation)?.clone())))
+        .collect())
+}
+
+/// Оставляет из `candidates` подстроки загрузок `uploads`, которые покрывают позицию
+/// `position` в строке `line_no` файла со строками `lines`, вместе с их размерами
+fn covering(
+    candidates: Vec<Range>,
+    uploads: &[Id<Upload>],
+    lines: &[Id<Line>],
+    line_no: usize,
+    position: u32,
+) -> Vec<((usize, u32), Range)> {
+    candidates
+        .into_iter()
+        .filter(|range| range.upload.is_some_and(|x| uploads.contains(&x)))
+        .filter_map(|range| {
+            // The query does not look at the lines of multi-line ranges
+            let (start, end) = if range.is_single_line() {
+                (line_no, line_no)
+            } else {
+                line_span(lines, &range)?
+            };
+            let covers = (start, range.start) <= (line_no, position)
+                && (line_no, position) < (end, range.end);
+            covers.then_some(((end - start, range.end.saturating_sub(range.start)), range))
+        })
+        .collect()
+}
+
+/// Идёт от узла по рёбрам `next`, пока не найдёт рёбра вида `edge`, и возвращает их концы
+#[instrument(skip(storage), err)]
+pub async fn follow(
+    storage: &Storage,
+    start: Id<Vertex>,
+    edge: &str,
+) -> Result<Vec<Vertex>, Report> {
+    let mut queue = Vec::new();
+    queue.push(start);
+    while let Some(vertex) = queue.pop() {
+        trace!("visiting vertex {:?}", vertex);
+        let outgoing: Vec<Edge> = storage
+            .find(
+                doc! {
+                    "data.edge": { "$eq": edge },
+                    "data.out_v": { "$eq": vertex }
+                },
+                None,
+            )
+            .await?;
+        let outgoing = outgoing
+            .iter()
+            .flat_map(|e| e.data.in_vs())
+            .collect::<Vec<_>>();
+        if !outgoing.is_empty() {
+            trace!("found matching edges: {:?}", outgoing);
+            return storage
+                .find::<Vertex>(
                     doc! {
-                        "data.edge": { "$eq": "Next" },
-                        "data.out_v": { "$eq": vertex }
+                        "_id": {
+                            "$in": outgoing
+                        }
                     },
                     None,
                 )
-                .await?;
-            trace!("following to next vertices: {:?}", next);
-            for i in next {
-                match i.data {
-                    EdgeInfo::Next(edge) => {
-                        queue.push(edge.in_v);
-                    }
-                    _ => return Err(eyre!("unexpected edge: {:?}", i).into()),
+                .await;
+        }
+
+        let next = storage
+            .find::<Edge>(
+                doc! {
+                    "data.edge": { "$eq": "Next" },
+                    "data.out_v": { "$eq": vertex }
+                },
+                None,
+            )
+            .await?;
+        trace!("following to next vertices: {:?}", next);
+        for i in next {
+            match i.data {
+                EdgeInfo::Next(edge) => {
+                    queue.push(edge.in_v);
                 }
+                _ => return Err(eyre!("unexpected edge: {:?}", i)),
             }
         }
     }
+    Ok(Vec::new())
+}
 
-    Ok(result)
+/// То же, что и [`follow`], но сразу для нескольких узлов: запросы делаются по одному
+/// на каждый шаг по рёбрам `next`, а не на каждый узел. Возвращает концы найденных рёбер
+/// для тех начальных узлов, у которых они есть.
+#[instrument(skip_all, fields(starts = starts.len(), edge = edge), err)]
+pub async fn follow_many(
+    storage: &Storage,
+    starts: &[Id<Vertex>],
+    edge: &str,
+) -> Result<HashMap<Id<Vertex>, Vec<Vertex>>, Report> {
+    let mut level = starts.iter().map(|&x| (x, x)).collect::<Vec<_>>();
+    let mut visited = level.iter().copied().collect::<HashSet<_>>();
+    let mut found = HashMap::<Id<Vertex>, Vec<Id<Vertex>>>::new();
+    while !level.is_empty() {
+        let vertices = level.iter().map(|(_, x)| *x).collect::<Vec<_>>();
+        let outgoing = storage
+            .find::<Edge>(
+                doc! {
+                    "data.edge": { "$eq": edge },
+                    "data.out_v": { "$in": &vertices },
+                },
+                None,
+            )
+            .await?;
+        let mut targets = HashMap::<Id<Vertex>, Vec<Id<Vertex>>>::new();
+        for e in &outgoing {
+            targets
+                .entry(e.data.out_v())
+                .or_default()
+                .extend(e.data.in_vs());
+        }
+        level.retain(|(start, vertex)| match targets.get(vertex) {
+            Some(x) => {
+                found.entry(*start).or_default().extend(x);
+                false
+            }
+            None => true,
+        });
+
+        let vertices = level.iter().map(|(_, x)| *x).collect::<Vec<_>>();
+        let next = storage
+            .find::<Edge>(
+                doc! {
+                    "data.edge": { "$eq": "Next" },
+                    "data.out_v": { "$in": vertices },
+                },
+                None,
+            )
+            .await?;
+        let mut next_of = HashMap::<Id<Vertex>, Vec<Id<Vertex>>>::new();
+        for e in next {
+            match e.data {
+                EdgeInfo::Next(x) => next_of.entry(x.out_v).or_default().push(x.in_v),
+                _ => return Err(eyre!("unexpected edge: {:?}", e)),
+            }
+        }
+        level = level
+            .iter()
+            .flat_map(|(start, vertex)| {
+                let next = next_of.get(vertex).into_iter().flatten();
+                next.map(move |x| (*start, *x))
+            })
+            .filter(|x| visited.insert(*x))
+            .collect();
+    }
+
+    let ids = found.values().flatten().copied().collect::<HashSet<_>>();
+    let vertices = storage
+        .find::<Vertex>(
+            doc! { "_id": { "$in": ids.into_iter().collect::<Vec<_>>() } },
+            None,
+        )
+        .await?
+        .into_iter()
+        .map(|x| (x.id, x))
+        .collect::<HashMap<_, _>>();
+    Ok(found
+        .into_iter()
+        .map(|(start, ends)| {
+            let ends = ends.iter().filter_map(|x| vertices.get(x).cloned());
+            (start, ends.collect())
+        })
+        .collect())
+}
+
+/// Находит номера первой и последней строк подстроки в списке строк файла
+pub fn line_span(lines: &[Id<Line>], range: &Range) -> Option<(usize, usize)> {
+    let start = lines.iter().position(|&x| x == range.start_line)?;
+    let end = start + lines[start..].iter().position(|&x| x == range.end_line)?;
+    Some((start, end))
 }
 
 #[instrument(skip_all, fields(range = ?range), ret, err)]
-pub async fn find_line_no(storage: &Storage, range: &Range) -> Result<u32, Report> {
-    let line_id = range.line_id;
+pub async fn find_line_nos(storage: &Storage, range: &Range) -> Result<(u32, u32), Report> {
     let file = match range.path.last().copied() {
         Some(x) => x,
         None => return Err(eyre!("range does not contains a path")),
@@ -228,11 +647,12 @@ pub async fn find_line_no(storage: &Storage, range: &Range) -> Result<u32, Repor
         Some(x) => x,
         None => return Err(eyre!("could not find {}", file)),
     };
-    let line_no = match doc.content {
-        FileContent::Text { lines, .. } => lines.iter().position(|&x| x == line_id).unwrap(),
+    let (start, end) = match doc.content {
+        FileContent::Text { lines, .. } => line_span(&lines, range)
+            .ok_or_else(|| eyre!("lines of {} not found in {}", range.id, file))?,
         _ => return Err(eyre!("expected text file, found {:?}", doc.content)),
     };
-    Ok(line_no as _)
+    Ok((start as _, end as _))
 }
 
 pub async fn find_file_path(storage: &Storage, range: &Range) -> Result<Vec<String>, Report> {
@@ -274,6 +694,105 @@ pub async fn find_file_path(storage: &Storage, range: &Range) -> Result<Vec<Stri
     Ok(path.into_iter().map(|x| x.to_owned()).collect())
 }
 
+/// Находит узлы документов активных загрузок, соответствующие файлу.
+///
+/// Одинаковые файлы разных коммитов хранятся одним узлом, поэтому документ находится
+/// и для коммитов, которые сами не проиндексированы. Документы ищутся через их подстроки,
+/// так что документы без подстрок не находятся.
+///
+/// Сначала идут документы загрузок самого `commit`, затем более новых загрузок.
+#[instrument(skip(storage), err)]
+pub async fn find_documents(
+    storage: &Storage,
+    commit: Id<Commit>,
+    node: Id<Node>,
+) -> eyre::Result<Vec<Vertex>> {
+    #[derive(Deserialize)]
+    struct First {
+        #[serde(rename = "_id")]
+        upload: Option<Id<Upload>>,
+        range: Id<Range>,
+    }
+
+    // One range of the file from each upload is enough to find the upload's document
+    let searchable = util::uploads::searchable(storage).await?;
+    let mut first = storage
+        .access::<Range>()
+        .aggregate(
+            [
+                doc! { "$match": { "path": node, "upload": searchable } },
+                doc! { "$group": { "_id": "$upload", "range": { "$first": "$_id" } } },
+            ],
+            None,
+        )
+        .await?
+        .with_type::<First>()
+        .try_collect::<Vec<_>>()
+        .await?;
+
+    let ranges = first.iter().map(|x| x.range).collect::<Vec<_>>();
+    let vertices = storage
+        .find::<Vertex>(
+            doc! {
+                "data.vertex": <&str>::from(VertexInfoDiscriminants::Range),
+                "data.range": { "$in": ranges },
+            },
+            None,
+        )
+        .await?
+        .into_iter()
+        .filter_map(|x| match x.data {
+            VertexInfo::Range { range, .. } => Some((range, x.id)),
+            _ => None,
+        })
+        .collect::<HashMap<_, _>>();
+    let contains = storage
+        .find::<Edge>(
+            doc! {
+                "data.edge": <&str>::from(EdgeInfoDiscriminants::Contains),
+                "data.in_vs": { "$in": vertices.values().collect::<Vec<_>>() },
+            },
+            None,
+        )
+        .await?;
+    let containers = contains
+        .iter()
+        .flat_map(|x| x.data.in_vs().map(|v| (v, x.data.out_v())))
+        .collect::<HashMap<_, _>>();
+    let mut documents = storage
+        .find::<Vertex>(
+            doc! { "_id": { "$in": containers.values().collect::<Vec<_>>() } },
+            None,
+        )
+        .await?
+        .into_iter()
+        .map(|x| (x.id, x))
+        .collect::<HashMap<_, _>>();
+
+    let uploads = storage
+        .find::<Upload>(
+            doc! { "_id": { "$in": first.iter().filter_map(|x| x.upload).collect::<Vec<_>>() } },
+            None,
+        )
+        .await?
+        .into_iter()
+        .map(|x| (x.id, x))
+        .collect::<HashMap<_, _>>();
+    first.sort_by_key(|x| {
+        let upload = x.upload.and_then(|x| uploads.get(&x));
+        let own = upload.is_some_and(|x| x.commits.contains(&commit));
+        (!own, Reverse(upload.map(|x| x.created_at)))
+    });
+
+    Ok(first
+        .iter()
+        .filter_map(|x| {
+            let vertex = vertices.get(&x.range)?;
+            documents.remove(containers.get(vertex)?)
+        })
+        .collect())
+}
+
 pub fn filter_vertices(
     vertices: impl IntoIterator<Item = Vertex>,
     kind: VertexInfoDiscriminants,
@@ -288,11 +807,101 @@ pub async fn find_items(
     storage: &Storage,
     results: impl Iterator<Item = Id<Vertex>>,
 ) -> Result<Vec<Range>, Report> {
-    let results = results.collect::<Vec<_>>();
-    if results.is_empty() {
+    let items = find_tagged_items(storage, results, None).await?;
+    Ok(items.into_iter().map(|(range, _)| range).collect())
+}
+
+/// То же, что и [`find_items`], но вместе со свойством ребра `item`, через которое найдена подстрока.
+/// Если указан `node`, возвращаются только подстроки этого файла.
+#[instrument(skip_all, err)]
+pub async fn find_tagged_items(
+    storage: &Storage,
+    results: impl Iterator<Item = Id<Vertex>>,
+    node: Option<Id<Node>>,
+) -> Result<Vec<(Range, Option<ItemKind>)>, Report> {
+    let mut queue = results.collect::<Vec<_>>();
+    if queue.is_empty() {
         return Ok(Vec::new());
     }
-    let items = storage
+    // Items with `referenceResults` and `implementationResults` properties point to other
+    // results, whose items are included as well
+    let mut visited = queue.iter().copied().collect::<HashSet<_>>();
+    let mut items = HashMap::new();
+    while !queue.is_empty() {
+        let edges = storage
+            .find::<Edge>(
+                doc! {
+                    "data.out_v": { "$in": &queue },
+                    "data.edge": { "$eq": <&str>::from(EdgeInfoDiscriminants::Item) }
+                },
+                None,
+            )
+            .await?;
+        queue.clear();
+        for edge in edges {
+            let item = match edge.data {
+                EdgeInfo::Item(x) => x,
+                _ => continue,
+            };
+            let in_vs = item.edge_data.in_vs;
+            match item.property {
+                Some(ItemKind::ReferenceResults | ItemKind::ImplementationResults) => {
+                    queue.extend(in_vs.into_iter().filter(|x| visited.insert(*x)))
+                }
+                Some(ItemKind::ReferenceLinks | ItemKind::ImplementationLinks) => {}
+                property => {
+                    for vertex in in_vs {
+                        items.entry(vertex).or_insert_with(|| property.clone());
+                    }
+                }
+            }
+        }
+    }
+    let vertices = storage
+        .find::<Vertex>(
+            doc! {
+                "_id": { "$in": items.keys().collect::<Vec<_>>() },
+                "data.vertex": { "$eq": <&str>::from(VertexInfoDiscriminants::Range) }
+            },
+            None,
+        )
+        .await?;
+    let properties = vertices
+        .into_iter()
+        .filter_map(|x| match x.data {
+            VertexInfo::Range { range, .. } => Some((range, items.remove(&x.id).flatten())),
+            _ => None,
+        })
+        .collect::<HashMap<_, _>>();
+    let mut filter = doc! {
+        "_id": { "$in": properties.keys().collect::<Vec<_>>() }
+    };
+    if let Some(node) = node {
+        filter.insert("path", node);
+    }
+    let ranges = storage.find::<Range>(filter, None).await?;
+    Ok(ranges
+        .into_iter()
+        .map(|x| {
+            let property = properties.get(&x.id).cloned().flatten();
+            (x, property)
+        })
+        .collect())
+}
+
+/// Подстроки, на которые ведут рёбра `item` каждого из результатов.
+///
+/// В отличие от [`find_items`], вложенные результаты не учитываются,
+/// поэтому подходит для результатов определений и объявлений.
+#[instrument(skip_all, err)]
+pub async fn find_items_by_result(
+    storage: &Storage,
+    results: &[Id<Vertex>],
+) -> Result<HashMap<Id<Vertex>, Vec<Range>>, Report> {
+    if results.is_empty() {
+        return Ok(HashMap::new());
+    }
+    let edges = storage
         .find::<Edge>(
             doc! {
                 "data.out_v": { "$in": results },
@@ -300,35 +909,42 @@ pub async fn find_items(
             },
             None,
         )
-        .await?
-        .into_iter()
-        .flat_map(|e| e.data.in_vs().collect::<Vec<_>>())
+        .await?;
+    let items = edges
+        .iter()
+        .flat_map(|e| e.data.in_vs().map(|x| (x, e.data.out_v())))
         .collect::<Vec<_>>();
-    let ranges = storage
+    let vertices = storage
         .find::<Vertex>(
             doc! {
-                "_id": { "$in": items},
+                "_id": { "$in": items.iter().map(|(x, _)| *x).collect::<Vec<_>>() },
                 "data.vertex": { "$eq": <&str>::from(VertexInfoDiscriminants::Range) }
             },
             None,
         )
-        .await?;
-    let ranges = ranges
+        .await?
         .into_iter()
         .filter_map(|x| match x.data {
-            VertexInfo::Range { range, .. } => Some(range),
+            VertexInfo::Range { range, .. } => Some((x.id, range)),
             _ => None,
         })
-        .collect::<Vec<_>>();
+        .collect::<HashMap<_, _>>();
     let ranges = storage
         .find::<Range>(
-            doc! {
-                "_id": { "$in": ranges }
-            },
+            doc! { "_id": { "$in": vertices.values().collect::<Vec<_>>() } },
             None,
         )
-        .await?;
-    Ok(ranges)
+        .await?
+        .into_iter()
+        .map(|x| (x.id, x))
+        .collect::<HashMap<_, _>>();
+    let mut result = HashMap::<_, Vec<_>>::new();
+    for (vertex, owner) in items {
+        if let Some(range) = vertices.get(&vertex).and_then(|x| ranges.get(x)) {
+            result.entry(owner).or_default().push(range.clone());
+        }
+    }
+    Ok(result)
 }
 
 pub async fn to_location(storage: &Storage, range: &Range) -> eyre::Result<lsp_types::Location> {
@@ -338,14 +954,190 @@ pub async fn to_location(storage: &Storage, range: &Range) -> eyre::Result<lsp_t
             .parse()
             .map_err(Report::new)
     };
-    let line_no = util::graph::find_line_no(storage, range);
-    let (path, line_no) = join!(path, line_no);
-    let (path, line_no) = (path?, line_no?);
+    let line_nos = util::graph::find_line_nos(storage, range);
+    let (path, line_nos) = join!(path, line_nos);
+    let (path, (start, end)) = (path?, line_nos?);
     Ok(lsp_types::Location {
         uri: path,
         range: lsp_types::Range {
-            start: Position::new(line_no as _, range.start),
-            end: Position::new(line_no as _, range.end),
+            start: Position::new(start, range.start),
+            end: Position::new(end, range.end),
         },
     })
 }
+
+/// Переводит подстроку из проиндексированного предка в `commit` через общие идентификаторы строк.
+/// Если файл или сами строки изменились, возвращается `None`, чтобы не показывать неверное расположение.
+pub async fn to_location_in(
+    storage: &Storage,
+    range: &Range,
+    commit: Id<Commit>,
+) -> eyre::Result<Option<lsp_types::Location>> {
+    let path = find_file_path(storage, range).await?;
+    let commit = storage
+        .get(commit)
+        .await?
+        .ok_or_eyre(eyre!("can't find {}", commit))?;
+    let path = path[1..].iter().map(|x| x.as_str()).collect::<Vec<_>>();
+    let lines = match resolve_path(storage, &commit, &path).await? {
+        Some(Node {
+            content: FileContent::Text { lines, .. },
+            ..
+        }) => lines,
+        _ => return Ok(None),
+    };
+    let indexed = match range.path.last() {
+        Some(&x) => storage.get(x).await?,
+        None => None,
+    };
+    let indexed = match indexed {
+        Some(Node {
+            content: FileContent::Text { lines, .. },
+            ..
+        }) => lines,
+        _ => return Ok(None),
+    };
+    let (start, end) = match line_span(&indexed, range) {
+        Some((start, end)) => (
+            translate_line(&indexed, start, &lines),
+            translate_line(&indexed, end, &lines),
+        ),
+        None => return Ok(None),
+    };
+    let (start, end) = match (start, end) {
+        (Some(start), Some(end)) if start <= end => (start, end),
+        _ => return Ok(None),
+    };
+    Ok(Some(lsp_types::Location {
+        uri: format!("bird:///{}/{}", commit.oid, path.join("/")).parse()?,
+        range: lsp_types::Range {
+            start: Position::new(start as _, range.start),
+            end: Position::new(end as _, range.end),
+        },
+    }))
+}
+
+/// Переводит расположение из проиндексированного предка в `commit` так же, как [`to_location_in`],
+/// для результатов, у которых нет сохранённой подстроки
+pub async fn translate_location(
+    storage: &Storage,
+    location: &lsp_types::Location,
+    commit: &Commit,
+) -> eyre::Result<Option<lsp_types::Location>> {
+    let indexed = match resolve_url(storage, &location.uri).await {
+        Ok(Node {
+            content: FileContent::Text { lines, .. },
+            ..
+        }) => lines,
+        Ok(_) | Err(ResolveError::FileNotFound { .. }) => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+    let path = location
+        .uri
+        .path()
+        .split('/')
+        .filter(|x| !x.is_empty())
+        .skip(1)
+        .collect::<Vec<_>>();
+    let lines = match resolve_path(storage, commit, &path).await? {
+        Some(Node {
+            content: FileContent::Text { lines, .. },
+            ..
+        }) => lines,
+        _ => return Ok(None),
+    };
+    let translate = |line: u32| translate_line(&indexed, line as usize, &lines);
+    let (start, end) = match (
+        translate(location.range.start.line),
+        translate(location.range.end.line),
+    ) {
+        (Some(start), Some(end)) if start <= end => (start, end),
+        _ => return Ok(None),
+    };
+    Ok(Some(lsp_types::Location {
+        uri: format!("bird:///{}/{}", commit.oid, path.join("/")).parse()?,
+        range: lsp_types::Range {
+            start: Position::new(start as _, location.range.start.character),
+            end: Position::new(end as _, location.range.end.character),
+        },
+    }))
+}
+
+/// Сколько соседних строк сравнивается при переводе строки между версиями файла
+const TRANSLATE_CONTEXT: usize = 20;
+
+/// Находит в `to` строку, соответствующую строке `line_no` из `from`.
+///
+/// Идентификатор может встретиться в файле несколько раз, если родители слияния перенесли строку
+/// в разные места, поэтому из нескольких вхождений выбирается то, вокруг которого совпадает
+/// больше соседних строк, а из равных ему — ближайшее к `line_no`.
+pub fn translate_line(from: &[Id<Line>], line_no: usize, to: &[Id<Line>]) -> Option<usize> {
+    let line = *from.get(line_no)?;
+    let context = |at: usize| {
+        let before = from[..line_no]
+            .iter()
+            .rev()
+            .zip(to[..at].iter().rev())
+            .take(TRANSLATE_CONTEXT)
+            .take_while(|(a, b)| a == b)
+            .count();
+        let after = from[line_no + 1..]
+            .iter()
+            .zip(&to[at + 1..])
+            .take(TRANSLATE_CONTEXT)
+            .take_while(|(a, b)| a == b)
+            .count();
+        before + after
+    };
+    to.iter()
+        .enumerate()
+        .filter(|(_, &x)| x == line)
+        .map(|(at, _)| at)
+        .max_by_key(|&at| (context(at), Reverse(at.abs_diff(line_no))))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn range(line: Id<Line>, upload: Option<Id<Upload>>) -> Range {
+        Range {
+            id: Id::new(),
+            start_line: line,
+            end_line: line,
+            path: Vec::new(),
+            start: 0,
+            end: 10,
+            upload,
+        }
+    }
+
+    #[test]
+    fn shared_line_keeps_uploads_apart() {
+        let lines = vec![Id::new(), Id::new()];
+        let (ours, theirs) = (Id::new(), Id::new());
+        let candidates = vec![
+            range(lines[1], Some(theirs)),
+            range(lines[1], Some(ours)),
+            range(lines[1], None),
+        ];
+        let found = covering(candidates, &[ours], &lines, 1, 4);
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].1.upload, Some(ours));
+    }
+
+    #[test]
+    fn multi_line_ranges_cover_inner_lines() {
+        let lines = vec![Id::new(), Id::new(), Id::new()];
+        let upload = Id::new();
+        let mut outer = range(lines[0], Some(upload));
+        outer.end_line = lines[2];
+        outer.start = 5;
+        outer.end = 1;
+        let found = covering(vec![outer.clone()], &[upload], &lines, 1, 0);
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].0, (2, 0));
+        assert!(covering(vec![outer.clone()], &[upload], &lines, 0, 4).is_empty());
+        assert!(covering(vec![outer], &[upload], &lines, 2, 1).is_empty());
+    }
+}
@@ -1 +1,5 @@
+pub mod encoding;
 pub mod graph;
+pub mod monikers;
+pub mod symbols;
+pub mod uploads;
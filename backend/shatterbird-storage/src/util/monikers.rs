@@ -0,0 +1,252 @@
+//! Индекс моникеров для навигации между репозиториями
+
+use std::collections::{HashMap, HashSet};
+
+use lsp_types::MonikerKind;
+use mongodb::bson::{self, doc};
+use tracing::{debug, instrument};
+
+use crate::model::lang::{EdgeInfo, VertexInfo};
+use crate::model::{Commit, Edge, SymbolMoniker, Upload, Vertex};
+use crate::{util, Id, Storage};
+
+/// Строит записи индекса для импортируемых и экспортируемых моникеров загрузки.
+/// Записи, построенные ранее, заменяются.
+#[instrument(skip(storage), err)]
+pub async fn index(storage: &Storage, upload: Id<Upload>) -> eyre::Result<usize> {
+    storage
+        .access::<SymbolMoniker>()
+        .delete_many(doc! { "upload": upload }, None)
+        .await?;
+
+    let vertices = storage
+        .find::<Vertex>(
+            doc! {
+                "upload": upload,
+                "data.vertex": { "$in": ["Moniker", "PackageInformation"] },
+            },
+            None,
+        )
+        .await?
+        .into_iter()
+        .map(|x| (x.id, x.data))
+        .collect::<HashMap<_, _>>();
+    let edges = storage
+        .find::<Edge>(
+            doc! {
+                "upload": upload,
+                "data.edge": { "$in": ["Moniker", "NextMoniker", "Attach", "PackageInformation"] },
+            },
+            None,
+        )
+        .await?;
+
+    let mut owners = HashMap::new();
+    let mut packages = HashMap::new();
+    let mut next = Vec::new();
+    for edge in edges {
+        match edge.data {
+            EdgeInfo::Moniker(x) => {
+                owners.insert(x.in_v, x.out_v);
+            }
+            EdgeInfo::PackageInformation(x) => {
+                packages.insert(x.out_v, x.in_v);
+            }
+            EdgeInfo::NextMoniker(x) => next.push((x.out_v, x.in_v)),
+            // An attached moniker names the symbol it is attached to
+            EdgeInfo::Attach(x) => next.push((x.in_v, x.out_v)),
+            _ => {}
+        }
+    }
+    // Monikers attached through `nextMoniker` or `attach` belong to the owner of the first one in the chain
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for (from, to) in &next {
+            if let (Some(&owner), false) = (owners.get(from), owners.contains_key(to)) {
+                owners.insert(*to, owner);
+                changed = true;
+            }
+        }
+    }
+
+    let mut entries = Vec::new();
+    for (&id, data) in &vertices {
+        let moniker = match data {
+            VertexInfo::Moniker(x) => x,
+            _ => continue,
+        };
+        let kind = match moniker.kind {
+            Some(x @ (MonikerKind::Import | MonikerKind::Export)) => x,
+            _ => continue,
+        };
+        let vertex = match owners.get(&id) {
+            Some(&x) => x,
+            None => continue,
+        };
+        let package = match packages.get(&id).and_then(|x| vertices.get(x)) {
+            Some(VertexInfo::PackageInformation(x)) => Some(x),
+            _ => None,
+        };
+        entries.push(SymbolMoniker {
+            id: Id::new(),
+            scheme: moniker.scheme.clone(),
+            identifier: moniker.identifier.clone(),
+            package: package.map(|x| x.name.clone()),
+            version: package.and_then(|x| x.version.clone()),
+            kind,
+            moniker: id,
+            vertex,
+            upload,
+        });
+    }
+    debug!("indexed {} monikers", entries.len());
+    if !entries.is_empty() {
+        storage.insert_many(entries.iter()).await?;
+    }
+    Ok(entries.len())
+}
+
+/// Находит записи индекса для узлов-моникеров, включая следующие за ними по `nextMoniker`
+/// и присоединённые к ним через `attach`
+#[instrument(skip_all, err)]
+pub async fn resolve(
+    storage: &Storage,
+    monikers: impl IntoIterator<Item = Id<Vertex>>,
+) -> eyre::Result<Vec<SymbolMoniker>> {
+    let all = chain(storage, monikers).await?;
+    storage
+        .find::<SymbolMoniker>(doc! { "moniker": { "$in": all } }, None)
+        .await
+}
+
+/// Дополняет узлы-моникеры следующими за ними по `nextMoniker` и присоединёнными к ним через `attach`
+#[instrument(skip_all, err)]
+pub async fn chain(
+    storage: &Storage,
+    monikers: impl IntoIterator<Item = Id<Vertex>>,
+) -> eyre::Result<Vec<Id<Vertex>>> {
+    let mut all = monikers.into_iter().collect::<Vec<_>>();
+    let mut queue = all.clone();
+    while !queue.is_empty() {
+        let next = storage
+            .find::<Edge>(
+                doc! {
+                    "$or": [
+                        { "data.edge": "NextMoniker", "data.out_v": { "$in": &queue } },
+                        { "data.edge": "Attach", "data.in_v": { "$in": &queue } },
+                    ]
+                },
+                None,
+            )
+            .await?;
+        queue = next
+            .iter()
+            .flat_map(|x| match &x.data {
+                EdgeInfo::Attach(x) => vec![x.out_v],
+                other => other.in_vs().collect(),
+            })
+            .filter(|x| !all.contains(x))
+            .collect();
+        all.extend(queue.iter().copied());
+    }
+    Ok(all)
+}
+
+/// Находит моникеры того же символа в других активных загрузках
+#[instrument(skip_all, err)]
+pub async fn matching(
+    storage: &Storage,
+    monikers: &[SymbolMoniker],
+    kind: Option<MonikerKind>,
+) -> eyre::Result<Vec<SymbolMoniker>> {
+    if monikers.is_empty() {
+        return Ok(Vec::new());
+    }
+    let mut upload = util::uploads::searchable(storage).await?;
+    let own = monikers.iter().map(|x| x.upload).collect::<Vec<_>>();
+    upload.insert("$nin", own);
+    let keys = monikers
+        .iter()
+        .map(|x| {
+            doc! {
+                "scheme": &x.scheme,
+                "identifier": &x.identifier,
+                "package": &x.package,
+                "version": &x.version,
+            }
+        })
+        .collect::<Vec<_>>();
+    let mut filter = doc! {
+        "$or": keys,
+        "upload": upload,
+    };
+    if let Some(kind) = kind {
+        filter.insert("kind", bson::to_bson(&kind)?);
+    }
+    storage.find::<SymbolMoniker>(filter, None).await
+}
+
+/// Находит моникер символа в самой новой активной загрузке, которая его экспортирует.
+///
+/// Загрузки упорядочиваются по истории их коммитов, а не по времени импорта, поэтому повторный
+/// импорт старого коммита ничего не меняет. Если указан `at`, подходят только загрузки его
+/// ближайшего проиндексированного предка, иначе — загрузки коммитов, не являющихся предками
+/// других подходящих коммитов. Из несвязанных историй выбирается коммит, сохранённый позже.
+/// Если символ нигде не экспортируется, подходят и импортирующие его загрузки.
+#[instrument(skip(storage, at), fields(at = ?at.map(|x| x.id)), err)]
+pub async fn latest(
+    storage: &Storage,
+    scheme: &str,
+    identifier: &str,
+    package: Option<&str>,
+    at: Option<&Commit>,
+) -> eyre::Result<Option<SymbolMoniker>> {
+    let mut filter = doc! {
+        "scheme": scheme,
+        "identifier": identifier,
+        "upload": util::uploads::searchable(storage).await?,
+    };
+    if let Some(package) = package {
+        filter.insert("package", package);
+    }
+    let mut found = storage.find::<SymbolMoniker>(filter, None).await?;
+    if found.iter().any(|x| x.kind == MonikerKind::Export) {
+        found.retain(|x| x.kind == MonikerKind::Export);
+    }
+    let uploads = storage
+        .find::<Upload>(
+            doc! { "_id": { "$in": found.iter().map(|x| x.upload).collect::<Vec<_>>() } },
+            None,
+        )
+        .await?
+        .into_iter()
+        .map(|x| (x.id, x))
+        .collect::<HashMap<_, _>>();
+
+    let commits = uploads
+        .values()
+        .flat_map(|x| x.commits.iter().copied())
+        .collect::<HashSet<_>>();
+    let allowed = match at {
+        Some(at) => util::graph::nearest_ancestor(storage, at, &commits)
+            .await?
+            .into_iter()
+            .collect(),
+        None => util::graph::newest_commits(storage, &commits).await?,
+    };
+    // Несколько загрузок одного коммита относятся к разным проектам, из них берётся последняя
+    Ok(found
+        .into_iter()
+        .filter_map(|x| {
+            let upload = uploads.get(&x.upload)?;
+            let commit = upload
+                .commits
+                .iter()
+                .filter(|x| allowed.contains(x))
+                .max()?;
+            Some(((*commit, upload.created_at), x))
+        })
+        .max_by_key(|(key, _)| *key)
+        .map(|(_, x)| x))
+}
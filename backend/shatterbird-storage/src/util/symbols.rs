@@ -0,0 +1,177 @@
+//! Поиск символов в рабочей области
+
+use futures::TryStreamExt;
+use mongodb::bson::{doc, Document};
+use tracing::instrument;
+
+use crate::model::{Commit, Symbol, Upload};
+use crate::{util, Storage};
+
+/// Найденные символы и коммит, в котором они искались
+#[derive(Debug)]
+pub struct SearchResult {
+    pub symbols: Vec<Symbol>,
+    /// Проиндексированный предок, если у самого коммита нет загрузок
+    pub ancestor: Option<Commit>,
+}
+
+/// Ищет символы, имена которых содержат все символы запроса по порядку без учёта регистра.
+/// Без коммита поиск идёт по всем используемым загрузкам.
+///
+/// Сначала идут символы, имена которых начинаются с запроса, затем остальные совпадения,
+/// внутри каждой группы более короткие имена идут раньше. Сортировка и пропуск `offset`
+/// выполняются базой данных, в память попадает не больше `limit` символов.
+#[instrument(skip(storage, commit), fields(commit = ?commit.map(|x| x.id)), err)]
+pub async fn search(
+    storage: &Storage,
+    commit: Option<&Commit>,
+    query: &str,
+    offset: usize,
+    limit: usize,
+) -> eyre::Result<SearchResult> {
+    let active = util::uploads::active(storage).await?;
+    let (filter, ancestor) = match commit {
+        None => (doc! { "upload": { "$in": active } }, None),
+        Some(commit) => match util::graph::find_indexed_ancestor(storage, commit).await? {
+            Some((ancestor, uploads)) => (
+                doc! { "commit": ancestor.id, "upload": { "$in": uploads } },
+                Some(ancestor),
+            ),
+            None => {
+                let uploads = storage
+                    .access::<Upload>()
+                    .distinct(
+                        "_id",
+                        doc! { "commits": commit.id, "_id": { "$in": active } },
+                        None,
+                    )
+                    .await?;
+                (
+                    doc! { "commit": commit.id, "upload": { "$in": uploads } },
+                    None,
+                )
+            }
+        },
+    };
+
+    let query = query
+        .chars()
+        .filter(|x| !x.is_whitespace())
+        .collect::<String>()
+        .to_lowercase();
+    let anchored = format!("^{}", regex_escape(&query));
+    let prefix = doc! { "$regex": &anchored };
+    let fuzzy = doc! { "$regex": fuzzy_pattern(&query), "$not": { "$regex": anchored } };
+
+    // Начинающиеся с запроса имена находятся по индексу, остальные совпадения идут за ними
+    let mut prefixed = filter.clone();
+    prefixed.insert("lowercase_name", prefix);
+    let prefixed_count = storage
+        .access::<Symbol>()
+        .count_documents(prefixed.clone(), None)
+        .await? as usize;
+    let mut symbols = Vec::new();
+    if offset < prefixed_count {
+        symbols = page(storage, prefixed, offset, limit).await?;
+    }
+    if symbols.len() < limit {
+        let mut rest = filter;
+        rest.insert("lowercase_name", fuzzy);
+        let offset = offset.saturating_sub(prefixed_count);
+        symbols.extend(page(storage, rest, offset, limit - symbols.len()).await?);
+    }
+    Ok(SearchResult { symbols, ancestor })
+}
+
+/// Символы, подходящие под фильтр, от коротких имён к длинным
+async fn page(
+    storage: &Storage,
+    filter: Document,
+    offset: usize,
+    limit: usize,
+) -> eyre::Result<Vec<Symbol>> {
+    let symbols = storage
+        .access::<Symbol>()
+        .aggregate(
+            [
+                doc! { "$match": filter },
+                doc! { "$addFields": { "length": { "$strLenCP": "$lowercase_name" } } },
+                doc! { "$sort": { "length": 1, "lowercase_name": 1, "_id": 1 } },
+                doc! { "$skip": offset as i64 },
+                doc! { "$limit": limit as i64 },
+                doc! { "$unset": "length" },
+            ],
+            None,
+        )
+        .await?
+        .with_type::<Symbol>()
+        .try_collect()
+        .await?;
+    Ok(symbols)
+}
+
+/// Регулярное выражение, которому соответствуют строки, содержащие символы запроса по порядку
+fn fuzzy_pattern(query: &str) -> String {
+    query
+        .chars()
+        .filter(|x| !x.is_whitespace())
+        .map(|x| regex_escape(x.encode_utf8(&mut [0; 4])))
+        .collect::<Vec<_>>()
+        .join(".*")
+}
+
+fn regex_escape(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    for ch in text.chars() {
+        if matches!(
+            ch,
+            '\\' | '.' | '+' | '*' | '?' | '(' | ')' | '|' | '[' | ']' | '{' | '}' | '^' | '$'
+        ) {
+            result.push('\\');
+        }
+        result.push(ch);
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use regex::Regex;
+
+    use super::*;
+
+    fn matches(query: &str, name: &str) -> bool {
+        Regex::new(&fuzzy_pattern(query)).unwrap().is_match(name)
+    }
+
+    #[test]
+    fn characters_in_order() {
+        assert_eq!(fuzzy_pattern("abc"), "a.*b.*c");
+        assert!(matches("fb", "foo_bar"));
+        assert!(matches("foo", "foo"));
+        assert!(!matches("bf", "foo_bar"));
+    }
+
+    #[test]
+    fn whitespace_is_ignored() {
+        assert_eq!(fuzzy_pattern(" a b\t"), "a.*b");
+        assert_eq!(fuzzy_pattern(""), "");
+        assert!(matches("  ", "anything"));
+    }
+
+    #[test]
+    fn special_characters_are_escaped() {
+        assert_eq!(fuzzy_pattern("a.b"), "a.*\\..*b");
+        assert!(matches("a+", "a+b"));
+        assert!(!matches("a+", "aa"));
+        assert!(matches("(x)", "f(x)"));
+        assert!(matches("$", "$x"));
+        assert!(!matches("[", "x"));
+    }
+
+    #[test]
+    fn non_ascii() {
+        assert_eq!(fuzzy_pattern("ый"), "ы.*й");
+        assert!(matches("пм", "привет_мир"));
+    }
+}
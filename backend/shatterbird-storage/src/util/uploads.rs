@@ -0,0 +1,236 @@
+//! Управление загрузками LSIF-дампов
+
+use std::collections::BTreeSet;
+
+use mongodb::bson::{self, doc, Bson};
+use mongodb::options::UpdateOptions;
+use tracing::{debug, info, instrument};
+
+use crate::model::{
+    ActiveUploads, Commit, Edge, Navigation, NavigationRange, Range, Symbol, SymbolMoniker, Upload,
+    UploadState, Vertex,
+};
+use crate::{util, Id, Storage};
+
+/// Делает загрузку доступной для поиска вместо предыдущих загрузок того же проекта для тех же коммитов.
+///
+/// Возвращает идентификаторы заменённых загрузок, их данные нужно удалить с помощью [`delete`].
+#[instrument(skip_all, fields(upload = %upload.id), err)]
+pub async fn activate(storage: &Storage, upload: &Upload) -> eyre::Result<Vec<Id<Upload>>> {
+    // Поиск видит только загрузки из одного документа, поэтому замена происходит одной записью.
+    // Запись проходит, только если с момента чтения список не менялся, иначе замена повторяется
+    let previous = loop {
+        let current = match storage.find_one::<ActiveUploads>(None, None).await? {
+            Some(x) => x,
+            None => {
+                create_active(storage).await?;
+                continue;
+            }
+        };
+        let previous = storage
+            .find::<Upload>(
+                doc! {
+                    "_id": { "$ne": upload.id, "$in": &current.uploads },
+                    "project": &upload.project,
+                    "commits": { "$in": &upload.commits },
+                },
+                None,
+            )
+            .await?
+            .into_iter()
+            .map(|x| x.id)
+            .collect::<Vec<_>>();
+        let uploads = current
+            .uploads
+            .iter()
+            .copied()
+            .filter(|x| *x != upload.id && !previous.contains(x))
+            .chain([upload.id])
+            .collect::<Vec<_>>();
+        // Документы, созданные до появления версий, не хранят нулевую версию
+        let version = match current.version {
+            0 => bson::bson!({ "$in": [0_i64, null] }),
+            x => bson::bson!(x),
+        };
+        let result = storage
+            .access::<ActiveUploads>()
+            .update_one(
+                doc! { "_id": current.id, "version": version },
+                doc! { "$set": { "uploads": uploads, "version": current.version + 1 } },
+                None,
+            )
+            .await?;
+        if result.matched_count == 1 {
+            break previous;
+        }
+        debug!("active uploads were changed concurrently, retrying");
+    };
+
+    // Состояния нужны только для отображения, поиск на них не смотрит
+    let mut changed = previous.clone();
+    changed.push(upload.id);
+    storage
+        .access::<Upload>()
+        .update_many(
+            doc! { "_id": { "$in": changed } },
+            vec![doc! {
+                "$set": {
+                    "state": {
+                        "$cond": [
+                            { "$eq": ["$_id", upload.id] },
+                            bson::to_bson(&UploadState::Ready)?,
+                            bson::to_bson(&UploadState::Superseded)?,
+                        ]
+                    }
+                }
+            }],
+            None,
+        )
+        .await?;
+    info!("upload is ready, superseded {} previous", previous.len());
+    Ok(previous)
+}
+
+/// Удаляет загрузку вместе со всеми её узлами, рёбрами, подстроками, моникерами,
+/// таблицей навигации и индексом символов
+#[instrument(skip(storage), err)]
+pub async fn delete(storage: &Storage, id: Id<Upload>) -> eyre::Result<()> {
+    let filter = doc! { "upload": id };
+    let (ranges, vertices, edges, monikers, navigation, navigation_ranges, symbols) = (
+        storage.access::<Range>(),
+        storage.access::<Vertex>(),
+        storage.access::<Edge>(),
+        storage.access::<SymbolMoniker>(),
+        storage.access::<Navigation>(),
+        storage.access::<NavigationRange>(),
+        storage.access::<Symbol>(),
+    );
+    let (ranges, vertices, edges, monikers, _, _, _) = futures::try_join!(
+        ranges.delete_many(filter.clone(), None),
+        vertices.delete_many(filter.clone(), None),
+        edges.delete_many(filter.clone(), None),
+        monikers.delete_many(filter.clone(), None),
+        navigation.delete_many(filter.clone(), None),
+        navigation_ranges.delete_many(filter.clone(), None),
+        symbols.delete_many(filter, None),
+    )?;
+    info!(
+        "deleted {} ranges, {} vertices, {} edges and {} monikers",
+        ranges.deleted_count, vertices.deleted_count, edges.deleted_count, monikers.deleted_count
+    );
+    storage
+        .access::<ActiveUploads>()
+        .update_many(doc! {}, doc! { "$pull": { "uploads": id } }, None)
+        .await?;
+    storage
+        .access::<Upload>()
+        .delete_one(doc! { "_id": id }, None)
+        .await?;
+    Ok(())
+}
+
+/// Символы документов, сохранённые с идентификаторами исходного дампа вместо подстрок
+fn unresolved_symbols() -> bson::Document {
+    doc! {
+        "data.vertex": "DocumentSymbolResult",
+        "data.result.id": { "$exists": true },
+    }
+}
+
+/// Возвращает загрузки, символы документов которых сохранены с идентификаторами исходного дампа.
+///
+/// Сопоставить такие символы с подстроками после импорта уже нельзя, загрузки нужно импортировать
+/// заново или очистить их символы с помощью [`drop_unresolved_symbols`].
+pub async fn with_unresolved_symbols(storage: &Storage) -> eyre::Result<Vec<Id<Upload>>> {
+    let uploads = storage
+        .access::<Vertex>()
+        .distinct("upload", unresolved_symbols(), None)
+        .await?;
+    Ok(uploads
+        .into_iter()
+        .filter_map(|x| bson::from_bson(x).ok())
+        .collect())
+}
+
+/// Очищает символы документов, найденные [`with_unresolved_symbols`].
+/// Возвращает количество изменённых документов.
+#[instrument(skip_all, err)]
+pub async fn drop_unresolved_symbols(storage: &Storage) -> eyre::Result<u64> {
+    let result = storage
+        .access::<Vertex>()
+        .update_many(
+            unresolved_symbols(),
+            doc! { "$set": { "data.result": [] } },
+            None,
+        )
+        .await?;
+    info!("dropped symbols of {} documents", result.modified_count);
+    Ok(result.modified_count)
+}
+
+/// Возвращает идентификаторы загрузок, которые используются при поиске
+pub async fn active(storage: &Storage) -> eyre::Result<Vec<Id<Upload>>> {
+    if let Some(active) = storage.find_one::<ActiveUploads>(None, None).await? {
+        return Ok(active.uploads);
+    }
+    ready(storage).await
+}
+
+/// Возвращает условие на поле `upload`, под которое подходят данные используемых загрузок
+/// и данные, сохранённые до появления загрузок, у которых этого поля нет
+pub async fn searchable(storage: &Storage) -> eyre::Result<bson::Document> {
+    let mut uploads = active(storage)
+        .await?
+        .into_iter()
+        .map(Bson::from)
+        .collect::<Vec<_>>();
+    uploads.push(Bson::Null);
+    Ok(doc! { "$in": uploads })
+}
+
+/// Загрузки, сохранённые до появления [`ActiveUploads`]
+async fn ready(storage: &Storage) -> eyre::Result<Vec<Id<Upload>>> {
+    Ok(storage
+        .find::<Upload>(doc! { "state": bson::to_bson(&UploadState::Ready)? }, None)
+        .await?
+        .into_iter()
+        .map(|x| x.id)
+        .collect())
+}
+
+/// Создаёт список используемых загрузок из готовых загрузок, если его ещё нет
+async fn create_active(storage: &Storage) -> eyre::Result<()> {
+    let uploads = ready(storage).await?;
+    storage
+        .access::<ActiveUploads>()
+        .update_one(
+            doc! {},
+            doc! { "$setOnInsert": { "uploads": uploads, "version": 0_i64 } },
+            UpdateOptions::builder().upsert(true).build(),
+        )
+        .await?;
+    Ok(())
+}
+
+/// Возвращает языки загрузок, по которым отвечают запросы к коммиту: его собственных
+/// или ближайшего проиндексированного предка. Без коммита учитываются все используемые загрузки.
+pub async fn languages(storage: &Storage, commit: Option<&Commit>) -> eyre::Result<Vec<String>> {
+    let active = active(storage).await?;
+    let filter = match commit {
+        None => doc! { "_id": { "$in": active } },
+        Some(commit) => match util::graph::find_indexed_ancestor(storage, commit).await? {
+            Some((_, uploads)) => doc! { "_id": { "$in": uploads } },
+            None => doc! { "_id": { "$in": active }, "commits": commit.id },
+        },
+    };
+    let languages = storage
+        .access::<Upload>()
+        .distinct("languages", filter, None)
+        .await?;
+    let languages = languages
+        .iter()
+        .filter_map(|x| x.as_str())
+        .map(|x| x.to_string())
+        .collect::<BTreeSet<_>>();
+    Ok(languages.into_iter().collect())
+}
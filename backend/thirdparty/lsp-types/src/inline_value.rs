@@ -132,6 +132,7 @@ pub struct InlineValueEvaluatableExpression {
 /// - directly as a text value (class InlineValueText).
 /// - as a name to use for a variable lookup (class InlineValueVariableLookup)
 /// - as an evaluatable expression (class InlineValueEvaluatableExpression)
+///
 /// The InlineValue types combines all inline value types into one type.
 ///
 /// @since 3.17.0
@@ -71,37 +71,37 @@ pub enum DocumentSymbolOrRangeBasedVec {
 #[serde(rename_all = "camelCase")]
 pub struct DefinitionTag {
     /// The text covered by the range     
-    text: String,
+    pub text: String,
     /// The symbol kind.
-    kind: crate::SymbolKind,
+    pub kind: crate::SymbolKind,
     /// Indicates if this symbol is deprecated.
     #[serde(default)]
     #[serde(skip_serializing_if = "std::ops::Not::not")]
-    deprecated: bool,
+    pub deprecated: bool,
     /// The full range of the definition not including leading/trailing whitespace but everything else, e.g comments and code.
     /// The range must be included in fullRange.
-    full_range: Range,
+    pub full_range: Range,
     /// Optional detail information for the definition.
     #[serde(skip_serializing_if = "Option::is_none")]
-    detail: Option<String>,
+    pub detail: Option<String>,
 }
 
 #[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct DeclarationTag {
     /// The text covered by the range     
-    text: String,
+    pub text: String,
     /// The symbol kind.
-    kind: crate::SymbolKind,
+    pub kind: crate::SymbolKind,
     /// Indicates if this symbol is deprecated.
     #[serde(default)]
-    deprecated: bool,
+    pub deprecated: bool,
     /// The full range of the definition not including leading/trailing whitespace but everything else, e.g comments and code.
     /// The range must be included in fullRange.
-    full_range: Range,
+    pub full_range: Range,
     /// Optional detail information for the definition.
     #[serde(skip_serializing_if = "Option::is_none")]
-    detail: Option<String>,
+    pub detail: Option<String>,
 }
 
 #[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
@@ -131,6 +131,10 @@ pub enum RangeTag {
 #[serde(tag = "label")]
 pub enum Vertex {
     MetaData(MetaData),
+    /// <https://microsoft.github.io/language-server-protocol/specifications/lsif/0.6.0/specification/#source>
+    Source(Source),
+    /// <https://microsoft.github.io/language-server-protocol/specifications/lsif/0.6.0/specification/#capabilities>
+    Capabilities(Capabilities),
     /// <https://github.com/Microsoft/language-server-protocol/blob/master/indexFormat/specification.md#the-project-vertex>
     Project(Project),
     Document(Document),
@@ -183,6 +187,7 @@ pub enum EventKind {
 pub enum EventScope {
     Document,
     Project,
+    MonikerAttach,
 }
 
 #[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
@@ -202,6 +207,8 @@ pub enum Edge {
     Next(EdgeData),
     PackageInformation(EdgeData),
     Item(Item),
+    /// Attaches a moniker of another dump to a moniker of this one
+    Attach(EdgeData),
 
     // Methods
     #[serde(rename = "textDocument/definition")]
@@ -254,7 +261,9 @@ pub enum ItemKind {
     Definitions,
     References,
     ReferenceResults,
+    ReferenceLinks,
     ImplementationResults,
+    ImplementationLinks,
 }
 
 #[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
@@ -273,6 +282,9 @@ pub struct Document {
     pub uri: Url,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub language_id: Option<String>,
+    /// The content of the document, base64 encoded
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub contents: Option<String>,
 }
 
 /// <https://github.com/Microsoft/language-server-protocol/blob/master/indexFormat/specification.md#result-set>
@@ -287,6 +299,8 @@ pub struct ResultSet {
 #[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct Project {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub resource: Option<Url>,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -303,7 +317,9 @@ pub struct MetaData {
     pub version: String,
 
     /// The project root (in form of an URI) used to compute this dump.
-    pub project_root: Url,
+    /// Deprecated since 0.6 in favour of [`Source::workspace_root`].
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub project_root: Option<Url>,
 
     /// The string encoding used to compute line and character values in
     /// positions and ranges.
@@ -314,6 +330,43 @@ pub struct MetaData {
     pub tool_info: Option<ToolInfo>,
 }
 
+/// Information about the source code the dump was computed from
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Source {
+    /// The workspace root (in form of an URI) used when indexing.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub workspace_root: Option<Url>,
+
+    /// Optional information about the repository containing the source code.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub repository: Option<Repository>,
+}
+
+/// Requests the dump has results for
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct Capabilities {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hover_provider: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub declaration_provider: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub definition_provider: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub type_definition_provider: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub references_provider: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub implementation_provider: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub document_symbol_provider: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub folding_range_provider: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub diagnostic_provider: Option<bool>,
+}
+
 #[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct Repository {
@@ -1,9 +1,7 @@
-#[test]
-#[cfg(unix)]
-fn run() {
+/// Checks that every entry survives parsing and serializing unchanged
+fn roundtrip(jsonl: &str) {
     use lsp_types::lsif::Entry;
 
-    let jsonl = include_str!("tsc-unix.lsif");
     for json in jsonl.lines() {
         let r = serde_json::from_str::<Entry>(json)
             .unwrap_or_else(|_| panic!("can not parse {}", json));
@@ -17,3 +15,28 @@ fn run() {
         );
     }
 }
+
+#[test]
+#[cfg(unix)]
+fn run() {
+    roundtrip(include_str!("tsc-unix.lsif"));
+}
+
+/// Elements introduced in LSIF 0.6: `source` and `capabilities` vertices, `attach` edges,
+/// events of every scope and item properties of implementation and reference results
+#[test]
+fn spec_0_6() {
+    roundtrip(include_str!("spec-0.6.lsif"));
+}
+
+#[test]
+fn metadata_without_project_root() {
+    use lsp_types::lsif::{Element, Entry, Vertex};
+
+    let json = r#"{"id":1,"type":"vertex","label":"metaData","version":"0.6.0","positionEncoding":"utf-16"}"#;
+    let entry = serde_json::from_str::<Entry>(json).unwrap();
+    match entry.data {
+        Element::Vertex(Vertex::MetaData(meta)) => assert_eq!(meta.project_root, None),
+        other => panic!("expected metaData, found {:?}", other),
+    }
+}
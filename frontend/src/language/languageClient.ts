@@ -1,4 +1,4 @@
-import {Disposable} from "vscode";
+import {Disposable, window} from "vscode";
 import {
     AbstractMessageReader, AbstractMessageWriter,
     BaseLanguageClient, DataCallback, Emitter, integer, Message,
@@ -49,7 +49,12 @@ class Writer extends AbstractMessageWriter implements MessageWriter {
         if (!Message.isRequest(msg)) {
             return;
         }
-        const {id, method, params} = msg;
+        const {id, method} = msg;
+        let params = msg.params;
+        if (method === 'workspace/symbol') {
+            // Symbols are searched in the commit of the active document
+            params = {...params, uri: window.activeTextEditor?.document.uri.toString()};
+        }
         fetch(`/api/lsp/${method}`, {
             method: 'POST',
             headers: {
@@ -57,6 +62,11 @@ class Writer extends AbstractMessageWriter implements MessageWriter {
             },
             body: JSON.stringify(params),
         }).then(async response => {
+            const ancestor = response.headers.get('X-Shatterbird-Approximate');
+            if (ancestor != null) {
+                // The commit is not indexed, answers are translated from an ancestor
+                window.setStatusBarMessage(`Approximate: information is taken from ${ancestor}`, 5000);
+            }
             const data = await response.json() as any;
             this.reader.reply({
                 jsonrpc: "2.0",
@@ -0,0 +1,6 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Дата и время в формате MongoDB Extended JSON
+ */
+export type DateTime = { $date: { $numberLong: string }, };
\ No newline at end of file
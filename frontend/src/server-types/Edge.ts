@@ -1,5 +1,6 @@
 // This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
 import type { Id } from "./Id";
+import type { Upload } from "./Upload";
 import type { Vertex } from "./Vertex";
 
 /**
@@ -17,7 +18,7 @@ data: {
 /**
  * Вид ребра
  */
-edge: "Contains" | "Moniker" | "NextMoniker" | "Next" | "PackageInformation" | "Item" | "Definition" | "Declaration" | "Hover" | "References" | "Implementation" | "TypeDefinition" | "FoldingRange" | "DocumentLink" | "DocumentSymbol" | "Diagnostic", 
+edge: "Contains" | "Moniker" | "NextMoniker" | "Next" | "PackageInformation" | "Item" | "Definition" | "Declaration" | "Hover" | "References" | "Implementation" | "TypeDefinition" | "FoldingRange" | "DocumentLink" | "DocumentSymbol" | "Diagnostic" | "Attach", 
 /**
  * Входящий узел, если один
  */
@@ -29,4 +30,8 @@ in_vs?: Array<Id<Vertex>>,
 /**
  * Исходящий узел
  */
-out_v: Id<Vertex>, }, };
\ No newline at end of file
+out_v: Id<Vertex>, }, 
+/**
+ * Загрузка, которой принадлежит ребро
+ */
+upload: Id<Upload> | null, };
\ No newline at end of file
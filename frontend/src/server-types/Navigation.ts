@@ -0,0 +1,37 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { Id } from "./Id";
+import type { NavigationTarget } from "./NavigationTarget";
+import type { Upload } from "./Upload";
+
+/**
+ * Результаты запросов для набора результатов, вычисленные при импорте,
+ * чтобы не обходить граф при каждом запросе.
+ *
+ * Подстроки одного символа обычно ведут в общий набор результатов, поэтому запись хранится
+ * один раз для набора, а подстроки ссылаются на неё через [`NavigationRange`]
+ */
+export type Navigation = { 
+/**
+ * Идентификатор объекта в базе данных
+ */
+_id: Id<Navigation>, 
+/**
+ * Загрузка, которой принадлежит набор результатов
+ */
+upload: Id<Upload>, 
+/**
+ * Содержимое подсказки
+ */
+hover: unknown, 
+/**
+ * Определения символа
+ */
+definitions: Array<NavigationTarget>, 
+/**
+ * Все использования символа, включая определения
+ */
+references: Array<NavigationTarget>, 
+/**
+ * Реализации символа
+ */
+implementations: Array<NavigationTarget>, };
\ No newline at end of file
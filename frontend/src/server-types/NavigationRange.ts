@@ -0,0 +1,26 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { Id } from "./Id";
+import type { Navigation } from "./Navigation";
+import type { Range } from "./Range";
+import type { Upload } from "./Upload";
+
+/**
+ * Связь подстроки с записью навигации, в которой лежат её результаты
+ */
+export type NavigationRange = { 
+/**
+ * Идентификатор объекта в базе данных
+ */
+_id: Id<NavigationRange>, 
+/**
+ * Подстрока, для которой вычислены результаты
+ */
+range: Id<Range>, 
+/**
+ * Запись с результатами
+ */
+navigation: Id<Navigation>, 
+/**
+ * Загрузка, которой принадлежит подстрока
+ */
+upload: Id<Upload>, };
\ No newline at end of file
@@ -0,0 +1,20 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { Id } from "./Id";
+import type { Range } from "./Range";
+
+/**
+ * Подстрока, на которую ведёт навигация, вместе с её расположением
+ */
+export type NavigationTarget = { 
+/**
+ * Подстрока
+ */
+range: Id<Range>, 
+/**
+ * Расположение подстроки в коммите загрузки
+ */
+location: unknown, 
+/**
+ * Свойство ребра `item`, через которое найдена подстрока
+ */
+kind: "declarations" | "definitions" | "references" | null, };
\ No newline at end of file
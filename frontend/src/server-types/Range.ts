@@ -1,6 +1,7 @@
 // This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
 import type { Id } from "./Id";
 import type { Line } from "./Line";
+import type { Upload } from "./Upload";
 
 /**
  * Описание подстроки в файле
@@ -11,18 +12,26 @@ export type Range = {
  */
 _id: Id<Range>, 
 /**
- * Идентификатор строки, в которой находится подстрока
+ * Идентификатор строки, в которой начинается подстрока
  */
-line_id: Id<Line>, 
+start_line: Id<Line>, 
+/**
+ * Идентификатор строки, в которой заканчивается подстрока
+ */
+end_line: Id<Line>, 
 /**
  * Полный путь к этому файлу
  */
 path: Array<Id<Line>>, 
 /**
- * Индекс первого символа подстроки
+ * Индекс первого символа подстроки в строке `start_line`
  */
 start: number, 
 /**
- * Индекс конца подстроки
+ * Индекс конца подстроки в строке `end_line`
+ */
+end: number, 
+/**
+ * Загрузка, которой принадлежит подстрока
  */
-end: number, };
\ No newline at end of file
+upload: Id<Upload> | null, };
\ No newline at end of file
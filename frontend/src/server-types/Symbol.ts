@@ -0,0 +1,46 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { Commit } from "./Commit";
+import type { Id } from "./Id";
+import type { Range } from "./Range";
+import type { Upload } from "./Upload";
+
+/**
+ * Запись индекса символов коммита, по которому работает поиск символов в рабочей области
+ */
+export type Symbol = { 
+/**
+ * Идентификатор объекта в базе данных
+ */
+_id: Id<Symbol>, 
+/**
+ * Имя символа
+ */
+name: string, 
+/**
+ * Имя символа в нижнем регистре, по нему идёт поиск
+ */
+lowercase_name: string, 
+/**
+ * Вид символа
+ */
+kind: number, 
+/**
+ * Имя символа, внутри которого определён этот
+ */
+container: string | null, 
+/**
+ * Расположение определения в коммите
+ */
+location: unknown, 
+/**
+ * Подстрока определения, если символ взят не из полного `DocumentSymbol`
+ */
+range: Id<Range> | null, 
+/**
+ * Коммит, в котором определён символ
+ */
+commit: Id<Commit>, 
+/**
+ * Загрузка, которой принадлежит символ
+ */
+upload: Id<Upload>, };
\ No newline at end of file
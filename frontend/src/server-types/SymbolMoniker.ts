@@ -0,0 +1,45 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { Id } from "./Id";
+import type { Upload } from "./Upload";
+import type { Vertex } from "./Vertex";
+
+/**
+ * Запись индекса моникеров: символ, который загрузка импортирует или экспортирует
+ */
+export type SymbolMoniker = { 
+/**
+ * Идентификатор объекта в базе данных
+ */
+_id: Id<SymbolMoniker>, 
+/**
+ * Схема моникера, например `tsc` или `rust-analyzer`
+ */
+scheme: string, 
+/**
+ * Идентификатор символа в пределах схемы
+ */
+identifier: string, 
+/**
+ * Название пакета, если моникер связан с `packageInformation`
+ */
+package: string | null, 
+/**
+ * Версия пакета
+ */
+version: string | null, 
+/**
+ * Импортируется символ или экспортируется
+ */
+kind: "import" | "export", 
+/**
+ * Узел с моникером
+ */
+moniker: Id<Vertex>, 
+/**
+ * Диапазон или `resultSet`, к которому относится моникер
+ */
+vertex: Id<Vertex>, 
+/**
+ * Загрузка, которой принадлежит моникер
+ */
+upload: Id<Upload>, };
\ No newline at end of file
@@ -0,0 +1,18 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Информация об инструменте, построившем LSIF-дамп
+ */
+export type ToolInfo = { 
+/**
+ * Название инструмента
+ */
+name: string, 
+/**
+ * Аргументы, с которыми был запущен инструмент
+ */
+args?: Array<string>, 
+/**
+ * Версия инструмента
+ */
+version?: string, };
\ No newline at end of file
@@ -0,0 +1,44 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { Commit } from "./Commit";
+import type { DateTime } from "./DateTime";
+import type { Id } from "./Id";
+import type { ToolInfo } from "./ToolInfo";
+import type { UploadRoot } from "./UploadRoot";
+import type { UploadState } from "./UploadState";
+
+/**
+ * Результат импорта одного LSIF-дампа (или нескольких, объединённых в один)
+ */
+export type Upload = { 
+/**
+ * Идентификатор объекта в базе данных
+ */
+_id: Id<Upload>, 
+/**
+ * Коммиты, к которым относятся документы дампа
+ */
+commits: Array<Id<Commit>>, 
+/**
+ * Сопоставление путей в дампе с коммитами
+ */
+roots: Array<UploadRoot>, 
+/**
+ * Проект, для которого построен дамп: имя или адрес вершин `project`, а если их нет, то `kind`
+ */
+project: string, 
+/**
+ * Языки проекта: значения `kind` вершин `project`
+ */
+languages: Array<string>, 
+/**
+ * Информация об инструменте, построившем дамп
+ */
+tool_info: ToolInfo | null, 
+/**
+ * Время начала импорта
+ */
+created_at: DateTime, 
+/**
+ * Состояние импорта
+ */
+state: UploadState, };
\ No newline at end of file
@@ -0,0 +1,16 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { Commit } from "./Commit";
+import type { Id } from "./Id";
+
+/**
+ * Префикс путей документов дампа, соответствующий корню коммита
+ */
+export type UploadRoot = { 
+/**
+ * Префикс пути
+ */
+prefix: string, 
+/**
+ * Коммит, в котором ищутся документы с этим префиксом
+ */
+commit: Id<Commit>, };
\ No newline at end of file
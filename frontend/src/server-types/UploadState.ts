@@ -0,0 +1,6 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Состояние импорта. При поиске используются только загрузки из [`ActiveUploads`]
+ */
+export type UploadState = "Processing" | "Ready" | "Superseded";
\ No newline at end of file
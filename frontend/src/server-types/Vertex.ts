@@ -1,5 +1,6 @@
 // This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
 import type { Id } from "./Id";
+import type { Upload } from "./Upload";
 
 /**
  * Узел графа
@@ -16,4 +17,8 @@ data: {
 /**
  * Вид узла
  */
-vertex: "MetaData" | "Project" | "Document" | "Range" | "ResultSet" | "Moniker" | "PackageInformation" | "DefinitionResult" | "DeclarationResult" | "TypeDefinitionResult" | "ReferenceResult" | "ImplementationResult" | "FoldingRangeResult" | "HoverResult" | "DocumentSymbolResult" | "DocumentLinkResult" | "DiagnosticResult", }, };
\ No newline at end of file
+vertex: "MetaData" | "Project" | "Document" | "Range" | "ResultSet" | "Moniker" | "PackageInformation" | "DefinitionResult" | "DeclarationResult" | "TypeDefinitionResult" | "ReferenceResult" | "ImplementationResult" | "FoldingRangeResult" | "HoverResult" | "DocumentSymbolResult" | "DocumentLinkResult" | "DiagnosticResult", }, 
+/**
+ * Загрузка, которой принадлежит узел
+ */
+upload: Id<Upload> | null, };
\ No newline at end of file